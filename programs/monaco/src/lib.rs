@@ -1,5 +1,8 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program::{invoke, invoke_signed};
 use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::solana_program::{system_instruction, system_program};
+use anchor_lang::Discriminator;
 use anchor_lending::cpi::{
     deposit_reserve_liquidity, redeem_reserve_collateral, DepositReserveLiquidity,
     RedeemReserveCollateral,
@@ -9,6 +12,10 @@ use anchor_spl::dex::serum_dex::instruction::SelfTradeBehavior;
 use anchor_spl::dex::serum_dex::matching::{OrderType, Side as SerumSide};
 use anchor_spl::dex::serum_dex::state::MarketState;
 use anchor_spl::token::{self, Mint, TokenAccount};
+use pyth_client::{AccountType, PriceStatus};
+use spl_associated_token_account::{create_associated_token_account, get_associated_token_address};
+use spl_token::instruction::AuthorityType;
+use spl_token::native_mint;
 use spl_token_lending::state::Reserve;
 use std::num::NonZeroU64;
 
@@ -18,48 +25,103 @@ declare_id!("BuYep31Y9ahB7qYPnTXY8zPVr4m341WPknmKj7RjGnaD");
 pub mod monaco {
     use super::*;
 
-    /// Deposits funds into solend reserve first, then makes corresponding DepositState account
+    /// Deposits funds into solend reserve first, then makes corresponding DepositState account.
+    /// Liquidity is pulled from the user's wallet into PDA owned token accounts created here.
+    /// A position NFT is minted to the user, whoever holds it owns the deposit
     pub fn deposit(
         ctx: Context<Deposit>,
         nonce: u8,
         liquidity_amount: u64,
         schedule: DcaSchedule,
-        dca_recipient: Pubkey,
+        index: u64,
+        bumps: DepositBumps,
     ) -> ProgramResult {
+        if !ctx
+            .accounts
+            .config
+            .is_whitelisted(ctx.accounts.lending.reserve.key)
+        {
+            return Err(ErrorCode::ReserveNotWhitelisted.into());
+        }
+        validate_reserve_mints(
+            &ctx.accounts.lending.reserve,
+            ctx.accounts.liquidity_mint.to_account_info().key,
+            ctx.accounts.lending.reserve_collateral_mint.key,
+        )?;
+        init_dca_recipient(
+            &ctx.accounts.user_authority,
+            &ctx.accounts.user_authority,
+            &ctx.accounts.dca_recipient,
+            &ctx.accounts.dca_mint.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.token_program,
+            &ctx.accounts.rent.to_account_info(),
+            &ctx.accounts.associated_token_program,
+        )?;
+
+        // Move liquidity from the user's wallet into the PDA owned liquidity account
+        fund_liquidity(
+            &ctx.accounts.user_authority,
+            &ctx.accounts.user_liquidity,
+            &ctx.accounts.source_liquidity.to_account_info(),
+            ctx.accounts.liquidity_mint.to_account_info().key,
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.token_program,
+            liquidity_amount,
+        )?;
+
         // Make deposit into lending program
-        let cpi_accounts = DepositReserveLiquidity {
-            lending_program: ctx.accounts.lending_program.clone(),
-            source_liquidity: ctx.accounts.source_liquidity.to_account_info().clone(),
-            destination_collateral_account: ctx
-                .accounts
-                .destination_collateral
-                .to_account_info()
-                .clone(),
-            reserve_account: ctx.accounts.reserve.clone(),
-            reserve_collateral_mint: ctx.accounts.reserve_collateral_mint.clone(),
-            reserve_liquidity_supply: ctx.accounts.reserve_liquidity_supply.clone(),
-            lending_market_account: ctx.accounts.lending_market.clone(),
-            lending_market_authority: ctx.accounts.lending_market_authority.clone(),
-            transfer_authority: ctx.accounts.transfer_authority.clone(),
-            clock: ctx.accounts.clock.to_account_info().clone(),
-            token_program_id: ctx.accounts.token_program.clone(),
-        };
-
-        let user_authority = ctx.accounts.user_authority.clone();
-        let reserve = ctx.accounts.reserve.clone();
+        let deposit_key = *ctx.accounts.deposit.to_account_info().key;
 
-        let pda_seeds = &[
-            &user_authority.key.to_bytes()[..32],
-            &reserve.key.to_bytes()[..32],
-            &[nonce],
-        ];
+        let pda_seeds = &[b"authority".as_ref(), deposit_key.as_ref(), &[nonce]];
         let pda_signer = &[&pda_seeds[..]];
-        let cpi_ctx = CpiContext::new_with_signer(
-            ctx.accounts.lending_program.clone(),
-            cpi_accounts,
+        ctx.accounts.lending.deposit(
+            &ctx.accounts.source_liquidity.to_account_info(),
+            &ctx.accounts.destination_collateral.to_account_info(),
+            &ctx.accounts.transfer_authority,
+            &ctx.accounts.clock.to_account_info(),
+            &ctx.accounts.token_program,
             pda_signer,
-        );
-        deposit_reserve_liquidity(cpi_ctx, liquidity_amount)?;
+            liquidity_amount,
+        )?;
+
+        // Mint the position NFT to the user and fix its supply at one
+        init_associated_account(
+            &ctx.accounts.user_authority,
+            &ctx.accounts.user_authority,
+            &ctx.accounts.position_token_account,
+            &ctx.accounts.position_mint.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.token_program,
+            &ctx.accounts.rent.to_account_info(),
+            &ctx.accounts.associated_token_program,
+        )?;
+        let mint_accounts = token::MintTo {
+            mint: ctx.accounts.position_mint.to_account_info(),
+            to: ctx.accounts.position_token_account.clone(),
+            authority: ctx.accounts.transfer_authority.clone(),
+        };
+        token::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.clone(),
+                mint_accounts,
+                pda_signer,
+            ),
+            1,
+        )?;
+        let authority_accounts = token::SetAuthority {
+            current_authority: ctx.accounts.transfer_authority.clone(),
+            account_or_mint: ctx.accounts.position_mint.to_account_info(),
+        };
+        token::set_authority(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.clone(),
+                authority_accounts,
+                pda_signer,
+            ),
+            AuthorityType::MintTokens,
+            None,
+        )?;
 
         // Build deposit state account
         let deposit_state_account = &mut ctx.accounts.deposit;
@@ -70,66 +132,69 @@ pub mod monaco {
         // maybe run an error check for zero collateral token account balance
 
         deposit_state_account.user_authority = *ctx.accounts.user_authority.key;
+        deposit_state_account.position_mint = *ctx.accounts.position_mint.to_account_info().key;
         deposit_state_account.collateral_account_key =
             *ctx.accounts.destination_collateral.to_account_info().key;
         deposit_state_account.liquidity_amount = liquidity_amount;
         deposit_state_account.collateral_amount = collateral_amount;
         deposit_state_account.schedule = schedule;
-        deposit_state_account.reserve_account = *ctx.accounts.reserve.key;
+        deposit_state_account.reserve_account = *ctx.accounts.lending.reserve.key;
+        deposit_state_account.liquidity_mint = *ctx.accounts.liquidity_mint.to_account_info().key;
         deposit_state_account.dca_mint = *ctx.accounts.dca_mint.to_account_info().key;
-        // dca_recipient should be the caller's ATA of the token they want to DCA into
-        deposit_state_account.dca_recipient = dca_recipient;
+        // dca_recipient is the caller's ATA of the token they want to DCA into, or the caller's
+        // wallet when DCA-ing into native SOL
+        deposit_state_account.dca_recipient = *ctx.accounts.dca_recipient.key;
         deposit_state_account.created_at = ctx.accounts.clock.unix_timestamp;
         deposit_state_account.counter = 0;
+        deposit_state_account.total_yield_spent = 0;
+        deposit_state_account.total_dca_received = 0;
         deposit_state_account.nonce = nonce;
+        deposit_state_account.index = index;
+        deposit_state_account.bumps = bumps;
         deposit_state_account.ooa = None;
+        deposit_state_account.version = DEPOSIT_STATE_VERSION;
+
+        // Empty execution history tied to this deposit
+        let history = &mut ctx.accounts.history;
+        history.deposit = *ctx.accounts.deposit.to_account_info().key;
+        history.bump = bumps.history;
 
         Ok(())
     }
 
-    /// Adds funds to an existing DepositState account. Requires user to supply the same
-    /// destination_collateral_account (controlled by PDA)
+    /// Adds funds to an existing DepositState account. Liquidity is pulled from the user's wallet
+    /// into the deposit's PDA owned liquidity account and deposited into the same collateral account
     pub fn add_to_deposit(
         ctx: Context<AddToDeposit>,
         nonce: u8,
         liquidity_amount: u64,
     ) -> ProgramResult {
-        let cpi_accounts = DepositReserveLiquidity {
-            lending_program: ctx.accounts.lending_program.clone(),
-            source_liquidity: ctx.accounts.source_liquidity.to_account_info().clone(),
-            destination_collateral_account: ctx
-                .accounts
-                .destination_collateral
-                .to_account_info()
-                .clone(),
-            reserve_account: ctx.accounts.reserve.clone(),
-            reserve_collateral_mint: ctx.accounts.reserve_collateral_mint.clone(),
-            reserve_liquidity_supply: ctx.accounts.reserve_liquidity_supply.clone(),
-            lending_market_account: ctx.accounts.lending_market.clone(),
-            lending_market_authority: ctx.accounts.lending_market_authority.clone(),
-            transfer_authority: ctx.accounts.transfer_authority.clone(),
-            clock: ctx.accounts.clock.to_account_info().clone(),
-            token_program_id: ctx.accounts.token_program.clone(),
-        };
-
-        let user_authority = ctx.accounts.user_authority.clone();
-        let reserve = ctx.accounts.reserve.clone();
+        // Move liquidity from the user's wallet into the PDA owned liquidity account
+        fund_liquidity(
+            &ctx.accounts.user_authority,
+            &ctx.accounts.user_liquidity,
+            &ctx.accounts.source_liquidity.to_account_info(),
+            &ctx.accounts.deposit_state.liquidity_mint,
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.token_program,
+            liquidity_amount,
+        )?;
 
-        let pda_seeds = &[
-            &user_authority.key.to_bytes()[..32],
-            &reserve.key.to_bytes()[..32],
-            &[nonce],
-        ];
-        let pda_signer = &[&pda_seeds[..]];
+        let deposit_key = *ctx.accounts.deposit_state.to_account_info().key;
 
-        let cpi_ctx = CpiContext::new_with_signer(
-            ctx.accounts.lending_program.clone(),
-            cpi_accounts,
-            pda_signer,
-        );
+        let pda_seeds = &[b"authority".as_ref(), deposit_key.as_ref(), &[nonce]];
+        let pda_signer = &[&pda_seeds[..]];
 
         // CPI to lending program instruction
-        deposit_reserve_liquidity(cpi_ctx, liquidity_amount)?;
+        ctx.accounts.lending.deposit(
+            &ctx.accounts.source_liquidity.to_account_info(),
+            &ctx.accounts.destination_collateral.to_account_info(),
+            &ctx.accounts.transfer_authority,
+            &ctx.accounts.clock.to_account_info(),
+            &ctx.accounts.token_program,
+            pda_signer,
+            liquidity_amount,
+        )?;
 
         let deposit_state = &mut ctx.accounts.deposit_state;
         deposit_state.liquidity_amount += liquidity_amount;
@@ -144,7 +209,7 @@ pub mod monaco {
 
     /// Privileged instruction for running DCA strat on a deposit account
     /// Admin is currently set to fee_receiver::ID
-    #[access_control(validate_admin(&ctx))]
+    #[access_control(validate_keeper(&ctx.accounts.keeper_registry, &ctx.accounts.user_authority))]
     pub fn run_dca_strategy<'info>(
         ctx: Context<'_, '_, '_, 'info, RunDcaStrategy<'info>>,
         nonce: u8,
@@ -167,66 +232,156 @@ pub mod monaco {
         // let refresh_cpi_ctx = CpiContext::new(ctx.accounts.solend.clone(), refresh_cpi_accounts);
         // refresh_reserve(refresh_cpi_ctx)?;
 
-        // Calculating how much collateral to redeem from reserve
-        let reserve_acct = &mut ctx.accounts.refreshed_reserve;
-        let deposit_state = &mut ctx.accounts.deposit_state;
-        let reserve: Reserve = Reserve::unpack(&reserve_acct.data.borrow())?;
+        // Basket deposits are run through run_basket_strategy
+        if ctx.accounts.deposit_state.basket_len > 0 {
+            return Err(ErrorCode::InvalidBasket.into());
+        }
+        let now = Clock::from_account_info(&ctx.accounts.clock)?.unix_timestamp;
+        ctx.accounts.deposit_state.check_runnable(now)?;
+        let schedule = ctx.accounts.deposit_state.schedule;
+        let intervals =
+            advance_schedule(&mut ctx.accounts.deposit_state.last_run_at, schedule, now)?;
 
-        let liquidity_in_collateral = reserve
-            .collateral_exchange_rate()?
-            .collateral_to_liquidity(deposit_state.collateral_amount)?;
+        // Oracle driven strategies price dca_mint in the liquidity token
+        let deposit_state = &ctx.accounts.deposit_state;
+        let price = if deposit_state.uses_oracle() {
+            if *ctx.accounts.price_oracle.key != deposit_state.price_oracle {
+                return Err(ErrorCode::InvalidOracle.into());
+            }
+            oracle_price(
+                &ctx.accounts.price_oracle,
+                deposit_state.liquidity_decimals,
+                deposit_state.dca_decimals,
+                Clock::from_account_info(&ctx.accounts.clock)?.slot,
+            )?
+        } else {
+            0
+        };
+
+        // Above the ceiling yield keeps accruing unspent, and is spent in one larger tranche
+        // once the price dips below it
+        if deposit_state.price_ceiling > 0 && price > deposit_state.price_ceiling {
+            return Err(ErrorCode::PriceAboveCeiling.into());
+        }
+
+        // Value averaging only spends what it takes to bring the position's value back onto
+        // its target path, the rest of the yield stays banked in the reserve. The path moves
+        // forward once per schedule interval elapsed, however late the run
+        let spend_cap = match deposit_state.strategy {
+            DcaStrategy::FixedSize => u64::MAX,
+            DcaStrategy::ValueAveraging {
+                value_per_run,
+                runs,
+            } => {
+                let target_value = (value_per_run as u128) * (runs as u128 + intervals as u128);
+                let position_value =
+                    (deposit_state.total_dca_received as u128) * (price as u128) / PRICE_PRECISION;
+                target_value
+                    .saturating_sub(position_value)
+                    .min(u64::MAX as u128) as u64
+            }
+        };
 
-        let amount_to_redeem = reserve
-            .collateral_exchange_rate()?
-            .liquidity_to_collateral(liquidity_in_collateral - deposit_state.liquidity_amount)?;
+        // Calculating how much collateral to redeem from reserve
+        let principal_amount = ctx.accounts.deposit_state.principal_per_run();
+        let (compounded_amount, amount_to_redeem) = harvestable_yield(
+            &ctx.accounts.lending.reserve,
+            ctx.accounts.deposit_state.collateral_amount,
+            ctx.accounts.deposit_state.liquidity_amount,
+            principal_amount,
+            ctx.accounts.deposit_state.compound_bps,
+            spend_cap,
+        )?;
+        let liquidity_before =
+            token::accessor::amount(&ctx.accounts.market.destination_liquidity.to_account_info())?;
 
         // Redeem reserve collateral
-        let redeem_cpi_accounts = RedeemReserveCollateral {
-            lending_program: ctx.accounts.lending_program.clone(),
-            source_collateral: ctx.accounts.source_collateral.to_account_info().clone(),
-            // This is the account that receives the liquidity, should be controlled by PDA authority
-            destination_liquidity: ctx
-                .accounts
-                .market
-                .destination_liquidity
-                .to_account_info()
-                .clone(),
-            refreshed_reserve_account: ctx.accounts.refreshed_reserve.clone(),
-            reserve_collateral_mint: ctx.accounts.reserve_collateral_mint.clone(),
-            reserve_liquidity: ctx.accounts.reserve_liquidity.clone(),
-            lending_market: ctx.accounts.lending_market.clone(),
-            lending_market_authority: ctx.accounts.lending_market_authority.clone(),
-            user_transfer_authority: ctx.accounts.transfer_authority.clone(),
-            clock: ctx.accounts.clock.clone(),
-            token_program_id: ctx.accounts.token_program_id.clone(),
-        };
-
-        let user_authority = ctx.accounts.user_authority.clone();
-        let reserve_account = ctx.accounts.refreshed_reserve.clone();
+        let deposit_key = *ctx.accounts.deposit_state.to_account_info().key;
 
-        let pda_seeds = &[
-            &user_authority.key.to_bytes()[..32],
-            &reserve_account.key.to_bytes()[..32],
-            &[nonce],
-        ];
+        let pda_seeds = &[b"authority".as_ref(), deposit_key.as_ref(), &[nonce]];
         let pda_signer = &[&pda_seeds[..]];
 
-        let redeem_cpi_ctx = CpiContext::new_with_signer(
-            ctx.accounts.lending_program.clone(),
-            redeem_cpi_accounts,
+        // The liquidity lands in the PDA controlled market wallet the order is paid from
+        if amount_to_redeem > 0 {
+            ctx.accounts.lending.redeem(
+                &ctx.accounts.source_collateral.to_account_info(),
+                &ctx.accounts.market.destination_liquidity.to_account_info(),
+                &ctx.accounts.transfer_authority,
+                &ctx.accounts.clock,
+                &ctx.accounts.token_program_id,
+                pda_signer,
+                amount_to_redeem,
+            )?;
+        }
+
+        // Spend the liquidity actually redeemed, not the collateral burned for it
+        let redeemed_amount =
+            token::accessor::amount(&ctx.accounts.market.destination_liquidity.to_account_info())?
+                .checked_sub(liquidity_before)
+                .ok_or(ErrorCode::MathOverflow)?;
+        let fee = charge_fee(
+            &ctx.accounts.config,
+            redeemed_amount,
+            &ctx.accounts.market.destination_liquidity.to_account_info(),
+            &ctx.accounts.fee_account.to_account_info(),
+            &ctx.accounts.transfer_authority,
+            &ctx.accounts.token_program_id,
             pda_signer,
-        );
-        redeem_reserve_collateral(redeem_cpi_ctx, amount_to_redeem)?;
+        )?;
+        let spend_amount = redeemed_amount
+            .checked_sub(fee)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let collateral_left =
+            token::accessor::amount(&ctx.accounts.source_collateral.to_account_info())?;
 
-        let (from_token, to_token) = match side {
-            Side::Bid => (
-                ctx.accounts.dca_recipient.to_account_info(),
-                ctx.accounts.market.destination_liquidity.to_account_info(),
-            ),
-            Side::Ask => (
-                ctx.accounts.market.destination_liquidity.to_account_info(),
-                ctx.accounts.dca_recipient.to_account_info(),
-            ),
+        // Nothing to buy this interval, e.g. value averaging at or above its target. The
+        // interval still counts, so the schedule and the value path move on
+        if spend_amount == 0 {
+            let deposit_account = &mut ctx.accounts.deposit_state;
+            deposit_account.advance_value_path(intervals);
+            deposit_account.settle_principal(principal_amount, compounded_amount)?;
+            deposit_account.collateral_amount = collateral_left;
+            ctx.accounts.keeper_stats.record_run(now);
+            return Ok(());
+        }
+
+        // Purchases of native SOL settle into a temporary wSOL account that is unwrapped
+        // to the recipient's system wallet once the swap is done. Purchases split between
+        // several recipients settle into a temporary proceeds account and are paid out from it
+        let native_payout = ctx.accounts.deposit_state.dca_mint == native_mint::ID;
+        let split_payout = ctx.accounts.deposit_state.split_count > 0;
+        let payout_prefix: &[u8] = if native_payout { b"wsol" } else { b"proceeds" };
+        let (payout_key, payout_bump) =
+            Pubkey::find_program_address(&[payout_prefix, deposit_key.as_ref()], ctx.program_id);
+        let payout_seeds = &[payout_prefix, deposit_key.as_ref(), &[payout_bump]];
+        if native_payout || split_payout {
+            if *ctx.accounts.serum_recipient.key != payout_key {
+                return Err(ErrorCode::InvalidRecipient.into());
+            }
+            create_payout_account(
+                &ctx.accounts.user_authority,
+                &ctx.accounts.serum_recipient,
+                &ctx.accounts.dca_mint,
+                &ctx.accounts.transfer_authority,
+                &ctx.accounts.rent,
+                &ctx.accounts.system_program.to_account_info(),
+                &ctx.accounts.token_program_id,
+                payout_seeds,
+            )?;
+        } else if *ctx.accounts.serum_recipient.key != ctx.accounts.deposit_state.dca_recipient
+            || token::accessor::mint(&ctx.accounts.serum_recipient)?
+                != ctx.accounts.deposit_state.dca_mint
+        {
+            return Err(ErrorCode::InvalidRecipient.into());
+        }
+
+        // Yield is always spent and the DCA token always received, the side only decides
+        // which of the two is the market's base currency
+        let from_token = ctx.accounts.market.destination_liquidity.to_account_info();
+        let to_token = ctx.accounts.serum_recipient.clone();
+        let (coin_wallet, pc_wallet) = match side {
+            Side::Bid => (&to_token, &from_token),
+            Side::Ask => (&from_token, &to_token),
         };
 
         // Token balances before the trade.
@@ -236,345 +391,4341 @@ pub mod monaco {
         // Initiate and settle Serum swap
         let orderbook: OrderbookClient<'info> = (&*ctx.accounts).into();
         match side {
-            Side::Bid => orderbook.buy(amount_to_redeem, None)?,
-            Side::Ask => orderbook.sell(amount_to_redeem, None)?,
+            Side::Bid => orderbook.buy(spend_amount, None)?,
+            Side::Ask => orderbook.sell(spend_amount, None)?,
         }
-        orderbook.settle(None, &ctx.accounts.dca_recipient)?;
+        orderbook.settle(None, coin_wallet, pc_wallet)?;
 
         // Token balances after the trade.
         let from_amount_after = token::accessor::amount(&from_token)?;
         let to_amount_after = token::accessor::amount(&to_token)?;
 
         //  Calculate the delta, i.e. the amount swapped.
-        let from_amount = from_amount_before.checked_sub(from_amount_after).unwrap();
-        let to_amount = to_amount_after.checked_sub(to_amount_before).unwrap();
+        let from_amount = from_amount_before
+            .checked_sub(from_amount_after)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let to_amount = to_amount_after
+            .checked_sub(to_amount_before)
+            .ok_or(ErrorCode::MathOverflow)?;
 
         // Run safety checks on serum swap
         apply_risk_checks(DidSwap {
             authority: *ctx.accounts.transfer_authority.key,
-            given_amount: amount_to_redeem,
+            given_amount: spend_amount,
             min_expected_swap_amount,
             from_amount,
             to_amount,
             spill_amount: 0,
             from_mint: token::accessor::mint(&from_token)?,
             to_mint: token::accessor::mint(&to_token)?,
-            quote_mint: match side {
-                Side::Bid => token::accessor::mint(&from_token)?,
-                Side::Ask => token::accessor::mint(&to_token)?,
-            },
+            quote_mint: token::accessor::mint(pc_wallet)?,
         })?;
 
+        // Reject fills too far below the oracle price, whatever the caller's expectation was
+        let max_slippage_bps = ctx.accounts.deposit_state.max_slippage_bps;
+        if max_slippage_bps > 0 {
+            let oracle_amount = mul_div(from_amount, PRICE_PRECISION as u64, price)?;
+            let min_amount = mul_div(
+                oracle_amount,
+                BPS_DENOMINATOR - max_slippage_bps as u64,
+                BPS_DENOMINATOR,
+            )?;
+            if to_amount < min_amount {
+                return Err(ErrorCode::SlippageExceeded.into());
+            }
+        }
+
+        // Lamports held by the wSOL account, the purchase plus the account's rent
+        let unwrapped_amount = ctx.accounts.serum_recipient.lamports();
+        if native_payout {
+            // Unwrap into the transfer authority and pay the purchase out from there, so the
+            // recipients get exactly what was bought and the rent goes back to the caller who
+            // paid it
+            let close_accounts = token::CloseAccount {
+                account: ctx.accounts.serum_recipient.clone(),
+                destination: ctx.accounts.transfer_authority.clone(),
+                authority: ctx.accounts.transfer_authority.clone(),
+            };
+            let close_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program_id.clone(),
+                close_accounts,
+                pda_signer,
+            );
+            token::close_account(close_ctx)?;
+        }
+
+        if split_payout {
+            // Split recipients are passed through remaining_accounts in the order they are
+            // stored, the last one takes the rounding remainder
+            let deposit_state = &ctx.accounts.deposit_state;
+            let splits = &deposit_state.splits[..deposit_state.split_count as usize];
+            if ctx.remaining_accounts.len() != splits.len() {
+                return Err(ErrorCode::InvalidRecipient.into());
+            }
+            let mut amount_left = to_amount;
+            for (i, (split, recipient)) in
+                splits.iter().zip(ctx.remaining_accounts.iter()).enumerate()
+            {
+                if *recipient.key != split.recipient {
+                    return Err(ErrorCode::InvalidRecipient.into());
+                }
+                let amount = if i == splits.len() - 1 {
+                    amount_left
+                } else {
+                    mul_div(to_amount, split.weight_bps as u64, BPS_DENOMINATOR)?
+                };
+                amount_left -= amount;
+
+                if native_payout {
+                    transfer_lamports(
+                        &ctx.accounts.transfer_authority,
+                        recipient,
+                        &ctx.accounts.system_program.to_account_info(),
+                        pda_signer,
+                        amount,
+                    )?;
+                } else {
+                    let transfer_accounts = token::Transfer {
+                        from: ctx.accounts.serum_recipient.clone(),
+                        to: recipient.clone(),
+                        authority: ctx.accounts.transfer_authority.clone(),
+                    };
+                    token::transfer(
+                        CpiContext::new_with_signer(
+                            ctx.accounts.token_program_id.clone(),
+                            transfer_accounts,
+                            pda_signer,
+                        ),
+                        amount,
+                    )?;
+                }
+            }
+
+            if !native_payout {
+                let close_accounts = token::CloseAccount {
+                    account: ctx.accounts.serum_recipient.clone(),
+                    destination: ctx.accounts.user_authority.clone(),
+                    authority: ctx.accounts.transfer_authority.clone(),
+                };
+                let close_ctx = CpiContext::new_with_signer(
+                    ctx.accounts.token_program_id.clone(),
+                    close_accounts,
+                    pda_signer,
+                );
+                token::close_account(close_ctx)?;
+            }
+        } else if native_payout {
+            transfer_lamports(
+                &ctx.accounts.transfer_authority,
+                &ctx.accounts.dca_recipient,
+                &ctx.accounts.system_program.to_account_info(),
+                pda_signer,
+                to_amount,
+            )?;
+        }
+
+        if native_payout {
+            // Refund the wSOL account's rent
+            transfer_lamports(
+                &ctx.accounts.transfer_authority,
+                &ctx.accounts.user_authority,
+                &ctx.accounts.system_program.to_account_info(),
+                pda_signer,
+                unwrapped_amount
+                    .checked_sub(to_amount)
+                    .ok_or(ErrorCode::MathOverflow)?,
+            )?;
+        }
+
         let deposit_account = &mut ctx.accounts.deposit_state;
         deposit_account.counter += 1;
+        deposit_account.advance_value_path(intervals);
+        // Compounded yield stays in the reserve as principal
+        deposit_account.settle_principal(principal_amount, compounded_amount)?;
+        deposit_account.collateral_amount = collateral_left;
+        deposit_account.total_yield_spent = deposit_account
+            .total_yield_spent
+            .checked_add(from_amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        deposit_account.total_dca_received = deposit_account
+            .total_dca_received
+            .checked_add(to_amount)
+            .ok_or(ErrorCode::MathOverflow)?;
         // This should only be not None on the first DCA, which can be checked client side by
         // decoding the deposit_state account
         if ooa != None {
             deposit_account.ooa = ooa;
         }
 
+        // Record the execution in the deposit's history ring buffer
+        ctx.accounts.history.push(ExecutionRecord {
+            timestamp: now,
+            amount_spent: from_amount,
+            amount_received: to_amount,
+            average_price: average_price(from_amount, to_amount),
+        });
+
+        ctx.accounts.keeper_stats.record_run(now);
+
         Ok(())
     }
 
+    /// Redeems all collateral and returns the liquidity to the user. Native SOL is unwrapped
+    /// straight into the user's wallet. The deposit's PDA token accounts are closed as well
     pub fn close_account(ctx: Context<CloseAccount>, nonce: u8) -> ProgramResult {
         let reserve_collateral = &mut ctx.accounts.source_collateral;
         let collateral_amount = token::accessor::amount(&reserve_collateral.to_account_info())?;
 
-        let redeem_cpi_accounts = RedeemReserveCollateral {
-            lending_program: ctx.accounts.lending_program.clone(),
-            source_collateral: ctx.accounts.source_collateral.to_account_info().clone(),
-            // This is the account that receives the liquidity, should be controlled by PDA authority
-            destination_liquidity: ctx.accounts.liquidity_recipient.to_account_info().clone(),
-            refreshed_reserve_account: ctx.accounts.refreshed_reserve.clone(),
-            reserve_collateral_mint: ctx.accounts.reserve_collateral_mint.clone(),
-            reserve_liquidity: ctx.accounts.reserve_liquidity.clone(),
-            lending_market: ctx.accounts.lending_market.clone(),
-            lending_market_authority: ctx.accounts.lending_market_authority.clone(),
-            user_transfer_authority: ctx.accounts.transfer_authority.clone(),
-            clock: ctx.accounts.clock.clone(),
-            token_program_id: ctx.accounts.token_program_id.clone(),
-        };
-
-        let user_authority = ctx.accounts.user_authority.clone();
-        let reserve_account = ctx.accounts.refreshed_reserve.clone();
+        let deposit_key = *ctx.accounts.deposit_state.to_account_info().key;
 
-        let pda_seeds = &[
-            &user_authority.key.to_bytes()[..32],
-            &reserve_account.key.to_bytes()[..32],
-            &[nonce],
-        ];
+        let pda_seeds = &[b"authority".as_ref(), deposit_key.as_ref(), &[nonce]];
         let pda_signer = &[&pda_seeds[..]];
 
-        let redeem_cpi_ctx = CpiContext::new_with_signer(
-            ctx.accounts.lending_program.clone(),
-            redeem_cpi_accounts,
+        // The liquidity lands in the PDA owned liquidity account before payout
+        ctx.accounts.lending.redeem(
+            &ctx.accounts.source_collateral.to_account_info(),
+            &ctx.accounts.source_liquidity.to_account_info(),
+            &ctx.accounts.transfer_authority,
+            &ctx.accounts.clock,
+            &ctx.accounts.token_program_id,
             pda_signer,
-        );
-        redeem_reserve_collateral(redeem_cpi_ctx, collateral_amount)?;
+            collateral_amount,
+        )?;
+
+        // Pay out the redeemed liquidity
+        if ctx.accounts.deposit_state.liquidity_mint != native_mint::ID {
+            if token::accessor::authority(&ctx.accounts.liquidity_recipient)?
+                != *ctx.accounts.user_authority.key
+            {
+                return Err(ErrorCode::InvalidRecipient.into());
+            }
+            let liquidity_amount =
+                token::accessor::amount(&ctx.accounts.source_liquidity.to_account_info())?;
+            let transfer_accounts = token::Transfer {
+                from: ctx.accounts.source_liquidity.to_account_info().clone(),
+                to: ctx.accounts.liquidity_recipient.clone(),
+                authority: ctx.accounts.transfer_authority.clone(),
+            };
+            let transfer_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program_id.clone(),
+                transfer_accounts,
+                pda_signer,
+            );
+            token::transfer(transfer_ctx, liquidity_amount)?;
+        }
+
+        // Closing the liquidity account unwraps any native SOL into the user's wallet
+        for account in &[
+            ctx.accounts.source_liquidity.to_account_info(),
+            ctx.accounts.source_collateral.to_account_info(),
+        ] {
+            let close_accounts = token::CloseAccount {
+                account: account.clone(),
+                destination: ctx.accounts.user_authority.clone(),
+                authority: ctx.accounts.transfer_authority.clone(),
+            };
+            let close_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program_id.clone(),
+                close_accounts,
+                pda_signer,
+            );
+            token::close_account(close_ctx)?;
+        }
+
+        // Burn the position NFT and give the holder back the rent of its token account
+        let burn_accounts = token::Burn {
+            mint: ctx.accounts.position_mint.to_account_info(),
+            to: ctx.accounts.position_token_account.to_account_info(),
+            authority: ctx.accounts.user_authority.clone(),
+        };
+        token::burn(
+            CpiContext::new(ctx.accounts.token_program_id.clone(), burn_accounts),
+            1,
+        )?;
+        let close_accounts = token::CloseAccount {
+            account: ctx.accounts.position_token_account.to_account_info(),
+            destination: ctx.accounts.user_authority.clone(),
+            authority: ctx.accounts.user_authority.clone(),
+        };
+        token::close_account(CpiContext::new(
+            ctx.accounts.token_program_id.clone(),
+            close_accounts,
+        ))?;
 
         Ok(())
     }
-}
 
-#[derive(Accounts)]
-#[instruction(nonce: u8, liquidity_amount: u64, _bump: u8)]
-pub struct Deposit<'info> {
-    // Deposit state account
-    #[account(init, payer = user_authority)]
-    pub deposit: Account<'info, DepositState>,
+    /// Changes the DCA schedule of a deposit. Owner or delegate
+    pub fn update_schedule(ctx: Context<ManageDeposit>, schedule: DcaSchedule) -> ProgramResult {
+        validate_manager(ctx.accounts)?;
+        ctx.accounts.deposit_state.schedule = schedule;
+        Ok(())
+    }
 
-    // AccountInfo of the account that calls the ix
-    #[account(signer)]
-    pub user_authority: AccountInfo<'info>,
+    /// Sets or removes the delegate allowed to tune the deposit's schedule, slippage and pause
+    /// state. The delegate can't touch principal or recipients
+    pub fn set_delegate(ctx: Context<UpdateDeposit>, delegate: Option<Pubkey>) -> ProgramResult {
+        ctx.accounts.deposit_state.delegate = delegate;
+        Ok(())
+    }
 
-    // Solend, Jet, or Port program
-    pub lending_program: AccountInfo<'info>,
+    /// Sets the Pyth price account of dca_mint quoted in the liquidity token, used by
+    /// oracle driven strategies and slippage checks
+    pub fn set_price_oracle(ctx: Context<SetStrategy>) -> ProgramResult {
+        oracle_price(
+            &ctx.accounts.price_oracle,
+            ctx.accounts.liquidity_mint.decimals,
+            ctx.accounts.dca_mint.decimals,
+            ctx.accounts.clock.slot,
+        )?;
 
-    // Token mint of DCA receiving asset
-    pub dca_mint: Account<'info, Mint>,
+        let deposit_state = &mut ctx.accounts.deposit_state;
+        deposit_state.price_oracle = *ctx.accounts.price_oracle.key;
+        deposit_state.liquidity_decimals = ctx.accounts.liquidity_mint.decimals;
+        deposit_state.dca_decimals = ctx.accounts.dca_mint.decimals;
+        Ok(())
+    }
 
-    // Solend CPI accounts
-    // Token account for asset to deposit into reserve and make sure account owner is transfer authority PDA
-    #[account(
-        constraint = source_liquidity.amount >= liquidity_amount,
-        constraint = source_liquidity.owner == *transfer_authority.key
-    )]
-    pub source_liquidity: Account<'info, TokenAccount>,
-    // Token account for reserve collateral token
-    // Make sure it has a 0 balance to ensure empty account and make sure account owner is transfer authority PDA
-    #[account(
-        constraint = destination_collateral.amount == 0,
-        constraint = destination_collateral.owner == *transfer_authority.key,
-    )]
-    pub destination_collateral: Account<'info, TokenAccount>,
-    // Reserve state account
-    pub reserve: AccountInfo<'info>,
-    // Token mint for reserve collateral token
-    pub reserve_collateral_mint: AccountInfo<'info>,
-    // Reserve liquidity supply SPL token account
-    pub reserve_liquidity_supply: AccountInfo<'info>,
-    // Lending market account
-    pub lending_market: AccountInfo<'info>,
-    // Lending market authority (PDA)
-    pub lending_market_authority: AccountInfo<'info>,
-    // Transfer authority for source_liquidity and desitnation_collateral accounts
-    #[account(seeds = [&user_authority.key.to_bytes()[..32], &reserve.key.to_bytes()[..32], &[nonce]], bump = _bump)]
-    pub transfer_authority: AccountInfo<'info>,
+    /// Caps how far below the oracle price a purchase may fill, in basis points. Requires the
+    /// deposit's price_oracle to be set. 0 removes the cap. Owner or delegate
+    pub fn set_max_slippage(ctx: Context<ManageDeposit>, max_slippage_bps: u16) -> ProgramResult {
+        validate_manager(ctx.accounts)?;
+        if max_slippage_bps as u64 > BPS_DENOMINATOR {
+            return Err(ErrorCode::InvalidBasisPoints.into());
+        }
+        if max_slippage_bps > 0 && ctx.accounts.deposit_state.price_oracle == Pubkey::default() {
+            return Err(ErrorCode::InvalidOracle.into());
+        }
+        // Basket runs don't read the oracle, so they refuse deposits that need it
+        if max_slippage_bps > 0 && ctx.accounts.deposit_state.basket_len > 0 {
+            return Err(ErrorCode::InvalidBasket.into());
+        }
+        ctx.accounts.deposit_state.max_slippage_bps = max_slippage_bps;
+        Ok(())
+    }
 
-    pub system_program: Program<'info, System>,
-    // Clock
-    pub clock: Sysvar<'info, Clock>,
-    // Token program
-    #[account(constraint = token_program.key == &token::ID)]
-    pub token_program: AccountInfo<'info>,
-}
+    /// Points future DCA purchases at another wallet's associated token account of the same
+    /// dca_mint, created if it does not exist yet, or at another system wallet when DCA-ing
+    /// into native SOL
+    pub fn update_recipient(ctx: Context<UpdateRecipient>) -> ProgramResult {
+        init_dca_recipient(
+            &ctx.accounts.user_authority,
+            &ctx.accounts.recipient_wallet,
+            &ctx.accounts.dca_recipient,
+            &ctx.accounts.dca_mint.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.token_program,
+            &ctx.accounts.rent.to_account_info(),
+            &ctx.accounts.associated_token_program,
+        )?;
+        ctx.accounts.deposit_state.dca_recipient = *ctx.accounts.dca_recipient.key;
+        Ok(())
+    }
 
-#[derive(Accounts)]
-#[instruction(nonce: u8, liquidity_amount: u64, _bump: u8)]
-pub struct AddToDeposit<'info> {
-    // Deposit state being modified
-    // has_one ensures only the creator of the deposit_state account can add to it
-    #[account(mut, has_one=user_authority)]
-    pub deposit_state: ProgramAccount<'info, DepositState>,
+    /// Opts a deposit into spending principal on top of its yield every run. Unspent principal
+    /// keeps earning yield in the reserve
+    pub fn set_principal_mode(ctx: Context<UpdateDeposit>, mode: PrincipalMode) -> ProgramResult {
+        if let PrincipalMode::Bps(bps) = mode {
+            if bps as u64 > BPS_DENOMINATOR {
+                return Err(ErrorCode::InvalidBasisPoints.into());
+            }
+        }
+        ctx.accounts.deposit_state.principal_mode = mode;
+        Ok(())
+    }
 
-    // Account calling the instruction
-    #[account(signer)]
-    pub user_authority: AccountInfo<'info>,
+    /// Sets the share of each harvest, in basis points, left in the reserve and added to the
+    /// deposit's principal instead of being swapped
+    pub fn set_compound_bps(ctx: Context<UpdateDeposit>, compound_bps: u16) -> ProgramResult {
+        if compound_bps as u64 > BPS_DENOMINATOR {
+            return Err(ErrorCode::InvalidBasisPoints.into());
+        }
+        ctx.accounts.deposit_state.compound_bps = compound_bps;
+        Ok(())
+    }
+
+    /// Switches a deposit between fixed size purchases and value averaging. Value averaging
+    /// targets a position worth `value_per_run` liquidity tokens more after every run, priced
+    /// by `price_oracle`, a Pyth price account of dca_mint quoted in the liquidity token.
+    /// The target path starts from the current position
+    pub fn set_strategy(ctx: Context<SetStrategy>, strategy: DcaStrategy) -> ProgramResult {
+        let deposit_state = &mut ctx.accounts.deposit_state;
+        deposit_state.strategy = match strategy {
+            DcaStrategy::FixedSize => DcaStrategy::FixedSize,
+            DcaStrategy::ValueAveraging { value_per_run, .. } => {
+                let price = oracle_price(
+                    &ctx.accounts.price_oracle,
+                    ctx.accounts.liquidity_mint.decimals,
+                    ctx.accounts.dca_mint.decimals,
+                    ctx.accounts.clock.slot,
+                )?;
+                let position_value =
+                    (deposit_state.total_dca_received as u128) * (price as u128) / PRICE_PRECISION;
+                let runs = if value_per_run == 0 {
+                    0
+                } else {
+                    (position_value / value_per_run as u128).min(u32::MAX as u128) as u32
+                };
+                deposit_state.price_oracle = *ctx.accounts.price_oracle.key;
+                DcaStrategy::ValueAveraging {
+                    value_per_run,
+                    runs,
+                }
+            }
+        };
+        deposit_state.liquidity_decimals = ctx.accounts.liquidity_mint.decimals;
+        deposit_state.dca_decimals = ctx.accounts.dca_mint.decimals;
+        Ok(())
+    }
 
-    // Solend, Jet, or Port program
-    pub lending_program: AccountInfo<'info>,
+    /// Stops runs of a deposit until it is resumed. Yield keeps accruing in the reserve.
+    /// Owner or delegate
+    pub fn pause_deposit(ctx: Context<ManageDeposit>) -> ProgramResult {
+        validate_manager(ctx.accounts)?;
+        ctx.accounts.deposit_state.paused = true;
+        Ok(())
+    }
 
-    // Solend CPI accounts
-    // Token account for asset to deposit into reserve and make sure account owner is transfer authority PDA
-    #[account(
-        constraint = source_liquidity.amount >= liquidity_amount,
-        constraint = source_liquidity.owner == *transfer_authority.key
-    )]
-    pub source_liquidity: Account<'info, TokenAccount>,
-    // Token account for reserve collateral token
-    // Make sure account owner is transfer authority PDA
-    #[account(
-        constraint = destination_collateral.owner == *transfer_authority.key,
-        // Destination collateral account should be deterministically derived for consistency - needs to be the same
-        // across all deposits to a deposit state account
-        constraint = *destination_collateral.to_account_info().key == deposit_state.collateral_account_key
-    )]
-    pub destination_collateral: Account<'info, TokenAccount>,
-    // Reserve state account
-    pub reserve: AccountInfo<'info>,
-    // Token mint for reserve collateral token
-    pub reserve_collateral_mint: AccountInfo<'info>,
-    // Reserve liquidity supply SPL token account
-    pub reserve_liquidity_supply: AccountInfo<'info>,
-    // Lending market account
-    pub lending_market: AccountInfo<'info>,
-    // Lending market authority (PDA)
-    pub lending_market_authority: AccountInfo<'info>,
-    // Transfer authority for accounts 1 and 2
-    #[account(seeds = [&user_authority.key.to_bytes()[..32], &reserve.key.to_bytes()[..32], &[nonce]], bump = _bump)]
-    pub transfer_authority: AccountInfo<'info>,
-    // Clock
-    pub clock: Sysvar<'info, Clock>,
-    // Token program
-    #[account(constraint = token_program.key == &token::ID)]
-    pub token_program: AccountInfo<'info>,
-}
+    /// Lets a paused deposit run again, spending the yield accrued while paused. Owner or
+    /// delegate
+    pub fn resume_deposit(ctx: Context<ManageDeposit>) -> ProgramResult {
+        validate_manager(ctx.accounts)?;
+        ctx.accounts.deposit_state.paused = false;
+        Ok(())
+    }
 
-#[derive(Accounts)]
-#[instruction(nonce: u8, _bump: u8)]
-pub struct RunDcaStrategy<'info> {
-    // Deposite state account being modified
-    #[account(mut)]
-    pub deposit_state: ProgramAccount<'info, DepositState>,
+    /// Bounds a deposit's DCA campaign. Runs are refused once `max_executions` runs happened,
+    /// `end_at` passed or `target_dca_amount` of dca_mint was received, whichever comes first,
+    /// and the deposit can be closed by anyone through close_completed. 0 disables a limit
+    pub fn set_end_conditions(
+        ctx: Context<UpdateDeposit>,
+        max_executions: u16,
+        end_at: i64,
+        target_dca_amount: u64,
+    ) -> ProgramResult {
+        let deposit_state = &mut ctx.accounts.deposit_state;
+        deposit_state.max_executions = max_executions;
+        deposit_state.end_at = end_at;
+        deposit_state.target_dca_amount = target_dca_amount;
+        deposit_state.check_target_reachable()
+    }
 
-    // Account calling the instruction
-    #[account(signer)]
-    pub user_authority: AccountInfo<'info>,
+    /// Permissionless crank closing a deposit that met its end conditions. The principal and
+    /// account rents go to the holder of the position NFT, who can burn the NFT afterwards
+    pub fn close_completed(ctx: Context<CloseCompleted>, nonce: u8) -> ProgramResult {
+        let now = Clock::from_account_info(&ctx.accounts.clock)?.unix_timestamp;
+        if !ctx.accounts.deposit_state.is_completed(now) {
+            return Err(ErrorCode::StrategyNotCompleted.into());
+        }
 
-    // Solend, Jet, or Port program
-    pub lending_program: AccountInfo<'info>,
+        let deposit_key = *ctx.accounts.deposit_state.to_account_info().key;
+        let pda_seeds = &[b"authority".as_ref(), deposit_key.as_ref(), &[nonce]];
+        let pda_signer = &[&pda_seeds[..]];
 
-    // Solana CPI accounts for RefreshReserve and RedeemReserveCollateral
+        let collateral_amount =
+            token::accessor::amount(&ctx.accounts.source_collateral.to_account_info())?;
+        ctx.accounts.lending.redeem(
+            &ctx.accounts.source_collateral.to_account_info(),
+            &ctx.accounts.source_liquidity.to_account_info(),
+            &ctx.accounts.transfer_authority,
+            &ctx.accounts.clock,
+            &ctx.accounts.token_program_id,
+            pda_signer,
+            collateral_amount,
+        )?;
 
-    // Refresh reserve accounts
-    // Reserve account
-    // pub reserve: AccountInfo<'info>,
-    // // Pyth reserve liquidity oracle
-    // // Must be the pyth price account specified in InitReserve
-    // pub pyth_reserve_liquidity_oracle: AccountInfo<'info>,
-    // // Switchboard Reserve liquidity oracle account
-    // // Must be the switchboard price account specified in InitReserve
-    // pub switchboard_reserve_liquidity_oracle: AccountInfo<'info>,
+        // Pay out the redeemed liquidity to the holder's ATA
+        let liquidity_mint = ctx.accounts.deposit_state.liquidity_mint;
+        if liquidity_mint != native_mint::ID {
+            if *ctx.accounts.liquidity_recipient.key
+                != get_associated_token_address(ctx.accounts.holder.key, &liquidity_mint)
+            {
+                return Err(ErrorCode::InvalidRecipient.into());
+            }
+            let liquidity_amount =
+                token::accessor::amount(&ctx.accounts.source_liquidity.to_account_info())?;
+            let transfer_accounts = token::Transfer {
+                from: ctx.accounts.source_liquidity.to_account_info(),
+                to: ctx.accounts.liquidity_recipient.clone(),
+                authority: ctx.accounts.transfer_authority.clone(),
+            };
+            let transfer_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program_id.clone(),
+                transfer_accounts,
+                pda_signer,
+            );
+            token::transfer(transfer_ctx, liquidity_amount)?;
+        }
 
-    // RedeeemReserveCollateral accounts
-    // Source token account for reserve collateral token
-    #[account(constraint = source_collateral.to_account_info().key == transfer_authority.key)]
-    pub source_collateral: Account<'info, TokenAccount>,
-    #[account(
-        mut,
-        constraint = *serum_recipient.to_account_info().key == deposit_state.dca_recipient
-    )]
-    pub serum_recipient: Account<'info, TokenAccount>,
-    // Refreshed reserve account
-    // #[account(constraint = refreshed_reserve.key == reserve.key)]
-    pub refreshed_reserve: AccountInfo<'info>,
-    // Reserve collateral mint account
-    pub reserve_collateral_mint: AccountInfo<'info>,
-    // Reserve liquidity supply SPL Token account.
-    pub reserve_liquidity: AccountInfo<'info>,
-    // Lending market account
-    pub lending_market: AccountInfo<'info>,
-    // Lending market authority - PDA
-    pub lending_market_authority: AccountInfo<'info>,
-    // User transfer authority
-    #[account(seeds = [&user_authority.key.to_bytes()[..32], &refreshed_reserve.key.to_bytes()[..32], &[nonce]], bump = _bump)]
-    pub transfer_authority: AccountInfo<'info>,
+        // Closing the liquidity account unwraps any native SOL into the holder's wallet
+        for account in &[
+            ctx.accounts.source_liquidity.to_account_info(),
+            ctx.accounts.source_collateral.to_account_info(),
+        ] {
+            let close_accounts = token::CloseAccount {
+                account: account.clone(),
+                destination: ctx.accounts.holder.clone(),
+                authority: ctx.accounts.transfer_authority.clone(),
+            };
+            let close_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program_id.clone(),
+                close_accounts,
+                pda_signer,
+            );
+            token::close_account(close_ctx)?;
+        }
 
-    // Serum swap accounts
-    market: MarketAccounts<'info>,
-    // DELET DIS
-    #[account(mut)]
-    dca_recipient: Account<'info, TokenAccount>,
-    // Programs.
-    dex_program: AccountInfo<'info>,
+        Ok(())
+    }
 
-    // Misc accounts - Leave at AccountInfo
-    pub clock: AccountInfo<'info>,
-    pub rent: AccountInfo<'info>,
-    pub token_program_id: AccountInfo<'info>,
-}
+    /// Makes runs conditional on the oracle price of dca_mint being at or below
+    /// `price_ceiling`, in native liquidity units per native dca_mint unit scaled by
+    /// PRICE_PRECISION. Yield accrues unspent in the meantime. 0 removes the ceiling
+    pub fn set_price_ceiling(ctx: Context<SetStrategy>, price_ceiling: u64) -> ProgramResult {
+        if price_ceiling > 0 {
+            // Make sure the oracle can be read before relying on it
+            oracle_price(
+                &ctx.accounts.price_oracle,
+                ctx.accounts.liquidity_mint.decimals,
+                ctx.accounts.dca_mint.decimals,
+                ctx.accounts.clock.slot,
+            )?;
+        }
 
-impl<'info> From<&RunDcaStrategy<'info>> for OrderbookClient<'info> {
-    fn from(accounts: &RunDcaStrategy<'info>) -> OrderbookClient<'info> {
-        OrderbookClient {
-            market: accounts.market.clone(),
-            authority: accounts.transfer_authority.clone(),
-            // pc_wallet: accounts.dca_recipient.to_account_info().clone(),
-            dex_program: accounts.dex_program.clone(),
-            token_program: accounts.token_program_id.clone(),
-            rent: accounts.rent.clone(),
+        let deposit_state = &mut ctx.accounts.deposit_state;
+        if price_ceiling > 0 {
+            deposit_state.price_oracle = *ctx.accounts.price_oracle.key;
+            deposit_state.liquidity_decimals = ctx.accounts.liquidity_mint.decimals;
+            deposit_state.dca_decimals = ctx.accounts.dca_mint.decimals;
         }
+        deposit_state.price_ceiling = price_ceiling;
+        Ok(())
     }
-}
 
-#[derive(Accounts)]
-#[instruction(nonce: u8, _bump: u8)]
-pub struct CloseAccount<'info> {
-    #[account(
-        mut,
-        close = user_authority,
-        constraint = deposit_state.user_authority == *user_authority.key
-    )]
-    pub deposit_state: Account<'info, DepositState>,
+    /// Splits every purchase across up to MAX_RECIPIENTS recipients by weight. The recipient
+    /// accounts are passed through `remaining_accounts` in the same order as `splits`, so their
+    /// mint can be checked. An empty list pays dca_recipient alone again
+    pub fn set_recipients(
+        ctx: Context<UpdateDeposit>,
+        splits: Vec<RecipientSplit>,
+    ) -> ProgramResult {
+        if splits.len() > MAX_RECIPIENTS || ctx.remaining_accounts.len() != splits.len() {
+            return Err(ErrorCode::InvalidRecipient.into());
+        }
+        if !splits.is_empty()
+            && splits.iter().map(|s| s.weight_bps as u64).sum::<u64>() != BPS_DENOMINATOR
+        {
+            return Err(ErrorCode::InvalidSplitWeights.into());
+        }
 
-    #[account(signer)]
-    pub user_authority: AccountInfo<'info>,
+        let deposit_state = &mut ctx.accounts.deposit_state;
+        for (split, recipient) in splits.iter().zip(ctx.remaining_accounts.iter()) {
+            if *recipient.key != split.recipient {
+                return Err(ErrorCode::InvalidRecipient.into());
+            }
+            validate_split_recipient(recipient, &deposit_state.dca_mint)?;
+        }
+
+        deposit_state.splits = Default::default();
+        deposit_state.splits[..splits.len()].copy_from_slice(&splits);
+        deposit_state.split_count = splits.len() as u8;
+        Ok(())
+    }
+
+    /// Spreads a deposit's yield across a basket of up to MAX_BASKET_LEGS target mints by
+    /// weight. Each leg's recipient token account is passed through `remaining_accounts` in the
+    /// same order as `legs`. An empty basket goes back to DCA-ing into dca_mint alone. A deposit
+    /// with a `target_dca_amount` needs a leg buying dca_mint
+    pub fn set_basket(ctx: Context<UpdateDeposit>, legs: Vec<BasketLegParams>) -> ProgramResult {
+        if legs.len() > MAX_BASKET_LEGS || ctx.remaining_accounts.len() != legs.len() {
+            return Err(ErrorCode::InvalidBasket.into());
+        }
+        if !legs.is_empty()
+            && legs.iter().map(|l| l.weight_bps as u64).sum::<u64>() != BPS_DENOMINATOR
+        {
+            return Err(ErrorCode::InvalidSplitWeights.into());
+        }
+
+        let deposit_state = &mut ctx.accounts.deposit_state;
+        // Basket runs don't read the oracle, so they refuse deposits that need it
+        if !legs.is_empty() && deposit_state.uses_oracle() {
+            return Err(ErrorCode::InvalidStrategy.into());
+        }
+        deposit_state.basket = Default::default();
+        for (i, (leg, recipient)) in legs.iter().zip(ctx.remaining_accounts.iter()).enumerate() {
+            // Legs always pay out to token accounts, wSOL included
+            if *recipient.owner != token::ID || token::accessor::mint(recipient)? != leg.mint {
+                return Err(ErrorCode::InvalidRecipient.into());
+            }
+            deposit_state.basket[i] = BasketLeg {
+                mint: leg.mint,
+                recipient: *recipient.key,
+                weight_bps: leg.weight_bps,
+                ooa: None,
+                total_received: 0,
+            };
+        }
+        deposit_state.basket_len = legs.len() as u8;
+        deposit_state.check_target_reachable()
+    }
+
+    /// Privileged instruction harvesting a basket deposit's yield and buying every leg of the
+    /// basket with its weighted part of it. Each leg's Serum accounts are passed through
+    /// `remaining_accounts` as a group of `BASKET_LEG_ACCOUNTS` accounts, in basket order:
+    ///
+    /// 0. `[writable]` Market
+    /// 1. `[writable]` Open orders, owned by the deposit's transfer authority
+    /// 2. `[writable]` Request queue
+    /// 3. `[writable]` Event queue
+    /// 4. `[writable]` Bids
+    /// 5. `[writable]` Asks
+    /// 6. `[writable]` Coin vault
+    /// 7. `[writable]` Pc vault
+    /// 8. `[]` Vault signer
+    /// 9. `[writable]` Recipient token account of the leg
+    ///
+    /// Every leg appends its own record to the deposit's history, in basket order. Only the
+    /// legs buying dca_mint count toward total_yield_spent and total_dca_received, so the
+    /// average entry price and end conditions stay denominated in dca_mint
+    #[access_control(validate_keeper(&ctx.accounts.keeper_registry, &ctx.accounts.user_authority))]
+    pub fn run_basket_strategy<'info>(
+        ctx: Context<'_, '_, '_, 'info, RunBasketStrategy<'info>>,
+        nonce: u8,
+        orders: Vec<BasketOrder>,
+    ) -> ProgramResult {
+        let basket_len = ctx.accounts.deposit_state.basket_len as usize;
+        if ctx.accounts.deposit_state.uses_oracle() {
+            return Err(ErrorCode::InvalidStrategy.into());
+        }
+        let now = Clock::from_account_info(&ctx.accounts.clock)?.unix_timestamp;
+        ctx.accounts.deposit_state.check_runnable(now)?;
+        let schedule = ctx.accounts.deposit_state.schedule;
+        advance_schedule(&mut ctx.accounts.deposit_state.last_run_at, schedule, now)?;
+        if basket_len == 0
+            || orders.len() != basket_len
+            || ctx.remaining_accounts.len() != basket_len * BASKET_LEG_ACCOUNTS
+        {
+            return Err(ErrorCode::InvalidBasket.into());
+        }
+
+        let principal_amount = ctx.accounts.deposit_state.principal_per_run();
+        let (compounded_amount, amount_to_redeem) = harvestable_yield(
+            &ctx.accounts.lending.reserve,
+            ctx.accounts.deposit_state.collateral_amount,
+            ctx.accounts.deposit_state.liquidity_amount,
+            principal_amount,
+            ctx.accounts.deposit_state.compound_bps,
+            u64::MAX,
+        )?;
+
+        let deposit_key = *ctx.accounts.deposit_state.to_account_info().key;
+        let pda_seeds = &[b"authority".as_ref(), deposit_key.as_ref(), &[nonce]];
+        let pda_signer = &[&pda_seeds[..]];
+
+        // Redeem the yield, and any principal due, into the deposit's liquidity account, which
+        // pays for every leg
+        let liquidity = ctx.accounts.source_liquidity.to_account_info();
+        let liquidity_before = token::accessor::amount(&liquidity)?;
+        ctx.accounts.lending.redeem(
+            &ctx.accounts.source_collateral.to_account_info(),
+            &liquidity,
+            &ctx.accounts.transfer_authority,
+            &ctx.accounts.clock,
+            &ctx.accounts.token_program_id,
+            pda_signer,
+            amount_to_redeem,
+        )?;
+        let redeemed_amount = token::accessor::amount(&liquidity)?
+            .checked_sub(liquidity_before)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let fee = charge_fee(
+            &ctx.accounts.config,
+            redeemed_amount,
+            &liquidity,
+            &ctx.accounts.fee_account.to_account_info(),
+            &ctx.accounts.transfer_authority,
+            &ctx.accounts.token_program_id,
+            pda_signer,
+        )?;
+        let yield_amount = redeemed_amount
+            .checked_sub(fee)
+            .ok_or(ErrorCode::MathOverflow)?;
+        if yield_amount == 0 {
+            return Err(ErrorCode::NoYieldToSpend.into());
+        }
+
+        let mut yield_left = yield_amount;
+        let mut dca_spent: u64 = 0;
+        let mut dca_received: u64 = 0;
+        for (i, (leg_accounts, order)) in ctx
+            .remaining_accounts
+            .chunks(BASKET_LEG_ACCOUNTS)
+            .zip(orders.iter())
+            .enumerate()
+        {
+            let leg = ctx.accounts.deposit_state.basket[i];
+            let recipient = &leg_accounts[9];
+            if *recipient.key != leg.recipient {
+                return Err(ErrorCode::InvalidRecipient.into());
+            }
+            match leg.ooa {
+                Some(ooa) if ooa != *leg_accounts[1].key => {
+                    return Err(ErrorCode::InvalidOpenOrders.into());
+                }
+                _ => {}
+            }
+
+            // The last leg takes the rounding remainder
+            let leg_amount = if i == basket_len - 1 {
+                yield_left
+            } else {
+                mul_div(yield_amount, leg.weight_bps as u64, BPS_DENOMINATOR)?
+            };
+            yield_left -= leg_amount;
+            // Too little yield for this leg's weight, it stays in the liquidity account
+            if leg_amount == 0 {
+                continue;
+            }
+
+            let (coin_wallet, pc_wallet) = match order.side {
+                Side::Bid => (recipient, &liquidity),
+                Side::Ask => (&liquidity, recipient),
+            };
+
+            // Token balances before the trade.
+            let from_amount_before = token::accessor::amount(&liquidity)?;
+            let to_amount_before = token::accessor::amount(recipient)?;
+
+            let orderbook = OrderbookClient {
+                market: MarketAccounts {
+                    market: leg_accounts[0].clone(),
+                    open_orders: leg_accounts[1].clone(),
+                    request_queue: leg_accounts[2].clone(),
+                    event_queue: leg_accounts[3].clone(),
+                    bids: leg_accounts[4].clone(),
+                    asks: leg_accounts[5].clone(),
+                    order_payer_token_account: liquidity.clone(),
+                    coin_vault: leg_accounts[6].clone(),
+                    pc_vault: leg_accounts[7].clone(),
+                    vault_signer: leg_accounts[8].clone(),
+                    destination_liquidity: ctx.accounts.source_liquidity.clone(),
+                },
+                authority: ctx.accounts.transfer_authority.clone(),
+                authority_seeds: vec![
+                    b"authority".to_vec(),
+                    deposit_key.to_bytes().to_vec(),
+                    vec![nonce],
+                ],
+                dex_program: ctx.accounts.dex_program.clone(),
+                token_program: ctx.accounts.token_program_id.clone(),
+                rent: ctx.accounts.rent.clone(),
+            };
+            match order.side {
+                Side::Bid => orderbook.buy(leg_amount, None)?,
+                Side::Ask => orderbook.sell(leg_amount, None)?,
+            }
+            orderbook.settle(None, coin_wallet, pc_wallet)?;
+
+            // Token balances after the trade.
+            let from_amount_after = token::accessor::amount(&liquidity)?;
+            let to_amount_after = token::accessor::amount(recipient)?;
+
+            //  Calculate the delta, i.e. the amount swapped.
+            let from_amount = from_amount_before
+                .checked_sub(from_amount_after)
+                .ok_or(ErrorCode::MathOverflow)?;
+            let to_amount = to_amount_after
+                .checked_sub(to_amount_before)
+                .ok_or(ErrorCode::MathOverflow)?;
+
+            // Run safety checks on serum swap
+            apply_risk_checks(DidSwap {
+                authority: *ctx.accounts.transfer_authority.key,
+                given_amount: leg_amount,
+                min_expected_swap_amount: order.min_expected_swap_amount,
+                from_amount,
+                to_amount,
+                spill_amount: 0,
+                from_mint: ctx.accounts.deposit_state.liquidity_mint,
+                to_mint: leg.mint,
+                quote_mint: token::accessor::mint(pc_wallet)?,
+            })?;
+
+            if leg.mint == ctx.accounts.deposit_state.dca_mint {
+                dca_spent = dca_spent
+                    .checked_add(from_amount)
+                    .ok_or(ErrorCode::MathOverflow)?;
+                dca_received = dca_received
+                    .checked_add(to_amount)
+                    .ok_or(ErrorCode::MathOverflow)?;
+            }
+            ctx.accounts.history.push(ExecutionRecord {
+                timestamp: now,
+                amount_spent: from_amount,
+                amount_received: to_amount,
+                average_price: average_price(from_amount, to_amount),
+            });
+            let stored_leg = &mut ctx.accounts.deposit_state.basket[i];
+            stored_leg.total_received = stored_leg
+                .total_received
+                .checked_add(to_amount)
+                .ok_or(ErrorCode::MathOverflow)?;
+            stored_leg.ooa = Some(*leg_accounts[1].key);
+        }
+
+        let collateral_left =
+            token::accessor::amount(&ctx.accounts.source_collateral.to_account_info())?;
+        let deposit_account = &mut ctx.accounts.deposit_state;
+        deposit_account.counter += 1;
+        // Compounded yield stays in the reserve as principal
+        deposit_account.settle_principal(principal_amount, compounded_amount)?;
+        deposit_account.collateral_amount = collateral_left;
+        deposit_account.total_yield_spent = deposit_account
+            .total_yield_spent
+            .checked_add(dca_spent)
+            .ok_or(ErrorCode::MathOverflow)?;
+        deposit_account.total_dca_received = deposit_account
+            .total_dca_received
+            .checked_add(dca_received)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        ctx.accounts.keeper_stats.record_run(now);
+
+        Ok(())
+    }
+
+    /// Switches the token a deposit DCAs into without touching its principal. The open orders
+    /// account of the previous market is closed, the new one gets set on the next DCA run.
+    /// Cost basis tracking restarts from zero since it is denominated in the old mint.
+    ///
+    /// Note the DepositState address stays derived from the mint it was created with
+    pub fn change_target_mint(ctx: Context<ChangeTargetMint>, nonce: u8) -> ProgramResult {
+        init_dca_recipient(
+            &ctx.accounts.user_authority,
+            &ctx.accounts.user_authority,
+            &ctx.accounts.dca_recipient,
+            &ctx.accounts.dca_mint.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.token_program,
+            &ctx.accounts.rent.to_account_info(),
+            &ctx.accounts.associated_token_program,
+        )?;
+
+        if let Some(ooa) = ctx.accounts.deposit_state.ooa {
+            if ooa != *ctx.accounts.open_orders.key {
+                return Err(ErrorCode::InvalidOpenOrders.into());
+            }
+
+            let deposit_key = *ctx.accounts.deposit_state.to_account_info().key;
+            let pda_seeds = &[b"authority".as_ref(), deposit_key.as_ref(), &[nonce]];
+            let pda_signer = &[&pda_seeds[..]];
+
+            // Open orders must be fully settled before they can be closed
+            let close_accounts = dex::CloseOpenOrders {
+                open_orders: ctx.accounts.open_orders.clone(),
+                authority: ctx.accounts.transfer_authority.clone(),
+                destination: ctx.accounts.user_authority.clone(),
+                market: ctx.accounts.market.clone(),
+            };
+            let close_ctx = CpiContext::new_with_signer(
+                ctx.accounts.dex_program.clone(),
+                close_accounts,
+                pda_signer,
+            );
+            dex::close_open_orders(close_ctx)?;
+        }
+
+        let deposit_state = &mut ctx.accounts.deposit_state;
+        deposit_state.dca_mint = *ctx.accounts.dca_mint.to_account_info().key;
+        deposit_state.dca_recipient = *ctx.accounts.dca_recipient.key;
+        // Split recipients hold the old mint, oracles price it and the purchase target counts
+        // it
+        deposit_state.split_count = 0;
+        deposit_state.strategy = DcaStrategy::FixedSize;
+        deposit_state.price_oracle = Pubkey::default();
+        deposit_state.price_ceiling = 0;
+        deposit_state.max_slippage_bps = 0;
+        deposit_state.target_dca_amount = 0;
+        deposit_state.ooa = None;
+        deposit_state.total_yield_spent = 0;
+        deposit_state.total_dca_received = 0;
+
+        deposit_state.check_target_reachable()
+    }
+
+    /// Hands a deposit over to a new owner by moving the position NFT into their ATA. The
+    /// collateral, liquidity and open orders accounts are controlled by the deposit's own PDA
+    /// authority, so nothing is redeemed and yield keeps accruing. Purchases go to the new
+    /// owner from now on: dca_recipient becomes their ATA of dca_mint, recipient splits are
+    /// dropped, and each basket leg pays their ATA of the leg's mint. Those leg accounts are
+    /// passed through `remaining_accounts` as (mint, ATA) pairs in basket order.
+    ///
+    /// A plain token transfer of the NFT hands over control of the deposit too, but leaves
+    /// user_authority and the recipients pointing at the previous owner. Either way the
+    /// DepositState address stays derived from the original depositor's wallet, so wallets
+    /// look up deposits they received by user_authority or position_mint instead.
+    pub fn transfer_deposit<'info>(
+        ctx: Context<'_, '_, '_, 'info, TransferDeposit<'info>>,
+    ) -> ProgramResult {
+        init_associated_account(
+            &ctx.accounts.user_authority,
+            &ctx.accounts.new_authority,
+            &ctx.accounts.new_position_token_account,
+            &ctx.accounts.position_mint.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.token_program,
+            &ctx.accounts.rent.to_account_info(),
+            &ctx.accounts.associated_token_program,
+        )?;
+
+        let transfer_accounts = token::Transfer {
+            from: ctx.accounts.position_token_account.to_account_info(),
+            to: ctx.accounts.new_position_token_account.clone(),
+            authority: ctx.accounts.user_authority.clone(),
+        };
+        let transfer_ctx = CpiContext::new(ctx.accounts.token_program.clone(), transfer_accounts);
+        token::transfer(transfer_ctx, 1)?;
+
+        init_dca_recipient(
+            &ctx.accounts.user_authority,
+            &ctx.accounts.new_authority,
+            &ctx.accounts.dca_recipient,
+            &ctx.accounts.dca_mint.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.token_program,
+            &ctx.accounts.rent.to_account_info(),
+            &ctx.accounts.associated_token_program,
+        )?;
+
+        let deposit_state = &mut ctx.accounts.deposit_state;
+        let basket_len = deposit_state.basket_len as usize;
+        if ctx.remaining_accounts.len() != basket_len * 2 {
+            return Err(ErrorCode::InvalidRecipient.into());
+        }
+        for (leg, accounts) in deposit_state.basket[..basket_len]
+            .iter_mut()
+            .zip(ctx.remaining_accounts.chunks(2))
+        {
+            if *accounts[0].key != leg.mint {
+                return Err(ErrorCode::InvalidRecipient.into());
+            }
+            init_associated_account(
+                &ctx.accounts.user_authority,
+                &ctx.accounts.new_authority,
+                &accounts[1],
+                &accounts[0],
+                &ctx.accounts.system_program.to_account_info(),
+                &ctx.accounts.token_program,
+                &ctx.accounts.rent.to_account_info(),
+                &ctx.accounts.associated_token_program,
+            )?;
+            leg.recipient = *accounts[1].key;
+        }
+
+        // The previous owner's delegate and recipients don't carry over
+        deposit_state.user_authority = *ctx.accounts.new_authority.key;
+        deposit_state.delegate = None;
+        deposit_state.dca_recipient = *ctx.accounts.dca_recipient.key;
+        deposit_state.splits = Default::default();
+        deposit_state.split_count = 0;
+        Ok(())
+    }
+
+    /// Pays out a deposit written before deposits were PDAs owned through a position NFT.
+    /// Those are keypair accounts whose collateral is owned by the PDA
+    /// [user_authority, reserve, nonce]. They don't load as DepositState and can't be resized
+    /// into it, so the collateral is redeemed, the liquidity paid to the depositor and the
+    /// account closed. The depositor can open a new deposit with the proceeds
+    pub fn close_legacy_deposit(ctx: Context<CloseLegacyDeposit>) -> ProgramResult {
+        let deposit_info = &ctx.accounts.deposit_state;
+        if deposit_info.owner != ctx.program_id {
+            return Err(ErrorCode::InvalidDepositVersion.into());
+        }
+        let legacy = LegacyDepositState::decode(&deposit_info.try_borrow_data()?)?;
+        if legacy.user_authority != *ctx.accounts.user_authority.key {
+            return Err(ErrorCode::InvalidManager.into());
+        }
+        if legacy.reserve_account != *ctx.accounts.lending.reserve.key
+            || legacy.collateral_account_key
+                != *ctx.accounts.source_collateral.to_account_info().key
+        {
+            return Err(ErrorCode::InvalidDepositVersion.into());
+        }
+
+        let pda_seeds = &[
+            legacy.user_authority.as_ref(),
+            legacy.reserve_account.as_ref(),
+            &[legacy.nonce],
+        ];
+        let authority = Pubkey::create_program_address(pda_seeds, ctx.program_id)
+            .map_err(|_| ErrorCode::InvalidDerivedAuthority)?;
+        if authority != *ctx.accounts.transfer_authority.key {
+            return Err(ErrorCode::InvalidDerivedAuthority.into());
+        }
+        let pda_signer = &[&pda_seeds[..]];
+
+        let collateral_amount =
+            token::accessor::amount(&ctx.accounts.source_collateral.to_account_info())?;
+        if collateral_amount > 0 {
+            ctx.accounts.lending.redeem(
+                &ctx.accounts.source_collateral.to_account_info(),
+                &ctx.accounts.source_liquidity.to_account_info(),
+                &ctx.accounts.transfer_authority,
+                &ctx.accounts.clock,
+                &ctx.accounts.token_program_id,
+                pda_signer,
+                collateral_amount,
+            )?;
+        }
+
+        // Pay out everything the legacy authority holds and give the depositor the rents back
+        let liquidity_amount =
+            token::accessor::amount(&ctx.accounts.source_liquidity.to_account_info())?;
+        let transfer_accounts = token::Transfer {
+            from: ctx.accounts.source_liquidity.to_account_info(),
+            to: ctx.accounts.liquidity_recipient.to_account_info(),
+            authority: ctx.accounts.transfer_authority.clone(),
+        };
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program_id.clone(),
+                transfer_accounts,
+                pda_signer,
+            ),
+            liquidity_amount,
+        )?;
+        for account in &[
+            ctx.accounts.source_liquidity.to_account_info(),
+            ctx.accounts.source_collateral.to_account_info(),
+        ] {
+            let close_accounts = token::CloseAccount {
+                account: account.clone(),
+                destination: ctx.accounts.user_authority.clone(),
+                authority: ctx.accounts.transfer_authority.clone(),
+            };
+            token::close_account(CpiContext::new_with_signer(
+                ctx.accounts.token_program_id.clone(),
+                close_accounts,
+                pda_signer,
+            ))?;
+        }
+
+        let user_authority = &ctx.accounts.user_authority;
+        **user_authority.lamports.borrow_mut() = user_authority
+            .lamports()
+            .checked_add(deposit_info.lamports())
+            .ok_or(ErrorCode::MathOverflow)?;
+        **deposit_info.lamports.borrow_mut() = 0;
+        deposit_info.try_borrow_mut_data()?.fill(0);
+        Ok(())
+    }
+
+    /// Privileged instruction running the DCA of many deposits sharing a reserve and Serum
+    /// market in a single order. Each deposit is passed through `remaining_accounts` as a group of
+    /// `BATCH_GROUP_LEN` accounts:
+    ///
+    /// 0. `[writable]` DepositState
+    /// 1. `[]` Deposit transfer authority (seeds ["authority", deposit_state, nonce])
+    /// 2. `[writable]` Deposit collateral token account
+    /// 3. `[writable]` dca_recipient of the deposit
+    /// 4. `[writable]` ExecutionHistory of the deposit
+    /// 5. `[writable]` Deposit liquidity token account (seeds ["liquidity", deposit_state])
+    ///
+    /// Proceeds, and any liquidity the order left unfilled, are split pro rata to the yield
+    /// each deposit contributed. Unfilled liquidity is refunded to the deposit's liquidity
+    /// account and paid out with the principal on close
+    #[access_control(validate_keeper(&ctx.accounts.keeper_registry, &ctx.accounts.user_authority))]
+    pub fn run_dca_batch<'info>(
+        ctx: Context<'_, '_, '_, 'info, RunDcaBatch<'info>>,
+        bump: u8,
+        side: Side,
+        min_expected_swap_amount: u64,
+    ) -> ProgramResult {
+        let groups = ctx.remaining_accounts;
+        if groups.is_empty() || groups.len() % BATCH_GROUP_LEN != 0 {
+            return Err(ErrorCode::InvalidBatch.into());
+        }
+        let now = Clock::from_account_info(&ctx.accounts.clock)?.unix_timestamp;
+
+        let batch_liquidity = ctx.accounts.market.destination_liquidity.to_account_info();
+        let batch_proceeds = ctx.accounts.batch_proceeds.to_account_info();
+        let dca_mint = ctx.accounts.batch_proceeds.mint;
+
+        // Redeem the yield of every deposit into the batch's liquidity account
+        let mut deposits = Vec::with_capacity(groups.len() / BATCH_GROUP_LEN);
+        let mut redeemed = Vec::with_capacity(deposits.capacity());
+        let mut total_redeemed: u64 = 0;
+        for (i, group) in groups.chunks(BATCH_GROUP_LEN).enumerate() {
+            // A deposit passed twice would be charged and credited twice
+            if groups
+                .chunks(BATCH_GROUP_LEN)
+                .take(i)
+                .any(|other| other[0].key == group[0].key)
+            {
+                return Err(ErrorCode::InvalidBatch.into());
+            }
+            let mut deposit_state: Account<'info, DepositState> = Account::try_from(&group[0])?;
+            let transfer_authority = &group[1];
+            let source_collateral = &group[2];
+            let dca_recipient = &group[3];
+            let deposit_liquidity = &group[5];
+
+            let authority_seeds = &[
+                b"authority".as_ref(),
+                group[0].key.as_ref(),
+                &[deposit_state.nonce],
+            ];
+            let expected_authority =
+                Pubkey::create_program_address(authority_seeds, ctx.program_id)
+                    .map_err(|_| ErrorCode::InvalidDerivedAuthority)?;
+            if *transfer_authority.key != expected_authority {
+                return Err(ErrorCode::InvalidDerivedAuthority.into());
+            }
+            let expected_liquidity = Pubkey::create_program_address(
+                &[
+                    b"liquidity".as_ref(),
+                    group[0].key.as_ref(),
+                    &[deposit_state.bumps.liquidity],
+                ],
+                ctx.program_id,
+            )
+            .map_err(|_| ErrorCode::InvalidBatch)?;
+            if deposit_state.reserve_account != *ctx.accounts.lending.reserve.key
+                || *source_collateral.key != deposit_state.collateral_account_key
+                || *deposit_liquidity.key != expected_liquidity
+            {
+                return Err(ErrorCode::InvalidBatch.into());
+            }
+            // Native SOL and split payouts need a per deposit payout account and stay on
+            // run_dca_strategy
+            if deposit_state.dca_mint != dca_mint
+                || deposit_state.split_count > 0
+                || deposit_state.basket_len > 0
+                || *dca_recipient.key != deposit_state.dca_recipient
+                || token::accessor::mint(dca_recipient)? != dca_mint
+            {
+                return Err(ErrorCode::InvalidRecipient.into());
+            }
+
+            // Oracle driven strategies decide on each deposit's order off its own price
+            if deposit_state.uses_oracle() {
+                return Err(ErrorCode::InvalidStrategy.into());
+            }
+            deposit_state.check_runnable(now)?;
+            let schedule = deposit_state.schedule;
+            advance_schedule(&mut deposit_state.last_run_at, schedule, now)?;
+
+            let principal_amount = deposit_state.principal_per_run();
+            let (compounded_amount, amount_to_redeem) = harvestable_yield(
+                &ctx.accounts.lending.reserve,
+                deposit_state.collateral_amount,
+                deposit_state.liquidity_amount,
+                principal_amount,
+                deposit_state.compound_bps,
+                u64::MAX,
+            )?;
+
+            let liquidity_before = token::accessor::amount(&batch_liquidity)?;
+            ctx.accounts.lending.redeem(
+                source_collateral,
+                &batch_liquidity,
+                transfer_authority,
+                &ctx.accounts.clock,
+                &ctx.accounts.token_program_id,
+                &[&authority_seeds[..]],
+                amount_to_redeem,
+            )?;
+            let amount = token::accessor::amount(&batch_liquidity)?
+                .checked_sub(liquidity_before)
+                .ok_or(ErrorCode::MathOverflow)?;
+
+            deposit_state.settle_principal(principal_amount, compounded_amount)?;
+            deposit_state.collateral_amount = token::accessor::amount(source_collateral)?;
+
+            total_redeemed = total_redeemed
+                .checked_add(amount)
+                .ok_or(ErrorCode::MathOverflow)?;
+            redeemed.push(amount);
+            deposits.push(deposit_state);
+        }
+
+        if total_redeemed == 0 {
+            return Err(ErrorCode::NoYieldToSpend.into());
+        }
+
+        let batch_seeds = &[b"batch".as_ref(), &[bump]];
+        let batch_signer = &[&batch_seeds[..]];
+        let fee = charge_fee(
+            &ctx.accounts.config,
+            total_redeemed,
+            &batch_liquidity,
+            &ctx.accounts.fee_account.to_account_info(),
+            &ctx.accounts.batch_authority,
+            &ctx.accounts.token_program_id,
+            batch_signer,
+        )?;
+        // Only this batch's yield is spent, whatever else the batch account holds
+        let spend_amount = total_redeemed
+            .checked_sub(fee)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        let (coin_wallet, pc_wallet) = match side {
+            Side::Bid => (&batch_proceeds, &batch_liquidity),
+            Side::Ask => (&batch_liquidity, &batch_proceeds),
+        };
+
+        // Token balances before the trade.
+        let from_amount_before = token::accessor::amount(&batch_liquidity)?;
+        let to_amount_before = token::accessor::amount(&batch_proceeds)?;
+
+        // One order for the whole batch
+        let orderbook = OrderbookClient {
+            market: ctx.accounts.market.clone(),
+            authority: ctx.accounts.batch_authority.clone(),
+            authority_seeds: vec![b"batch".to_vec(), vec![bump]],
+            dex_program: ctx.accounts.dex_program.clone(),
+            token_program: ctx.accounts.token_program_id.clone(),
+            rent: ctx.accounts.rent.clone(),
+        };
+        match side {
+            Side::Bid => orderbook.buy(spend_amount, None)?,
+            Side::Ask => orderbook.sell(spend_amount, None)?,
+        }
+        orderbook.settle(None, coin_wallet, pc_wallet)?;
+
+        // Token balances after the trade.
+        let from_amount_after = token::accessor::amount(&batch_liquidity)?;
+        let to_amount_after = token::accessor::amount(&batch_proceeds)?;
+
+        //  Calculate the delta, i.e. the amount swapped.
+        let from_amount = from_amount_before
+            .checked_sub(from_amount_after)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let to_amount = to_amount_after
+            .checked_sub(to_amount_before)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let unfilled_amount = spend_amount
+            .checked_sub(from_amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        // Run safety checks on serum swap
+        apply_risk_checks(DidSwap {
+            authority: *ctx.accounts.batch_authority.key,
+            given_amount: spend_amount,
+            min_expected_swap_amount,
+            from_amount,
+            to_amount,
+            spill_amount: 0,
+            from_mint: token::accessor::mint(&batch_liquidity)?,
+            to_mint: dca_mint,
+            quote_mint: token::accessor::mint(pc_wallet)?,
+        })?;
+
+        // Distribute the proceeds and refunds pro rata
+        let spent_shares = pro_rata(from_amount, &redeemed)?;
+        let received_shares = pro_rata(to_amount, &redeemed)?;
+        let refund_shares = pro_rata(unfilled_amount, &redeemed)?;
+        for (i, (group, mut deposit_state)) in groups
+            .chunks(BATCH_GROUP_LEN)
+            .zip(deposits.into_iter())
+            .enumerate()
+        {
+            let spent = spent_shares[i];
+            let received = received_shares[i];
+
+            let transfer_accounts = token::Transfer {
+                from: batch_proceeds.clone(),
+                to: group[3].clone(),
+                authority: ctx.accounts.batch_authority.clone(),
+            };
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program_id.clone(),
+                    transfer_accounts,
+                    batch_signer,
+                ),
+                received,
+            )?;
+            if refund_shares[i] > 0 {
+                let refund_accounts = token::Transfer {
+                    from: batch_liquidity.clone(),
+                    to: group[5].clone(),
+                    authority: ctx.accounts.batch_authority.clone(),
+                };
+                token::transfer(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.token_program_id.clone(),
+                        refund_accounts,
+                        batch_signer,
+                    ),
+                    refund_shares[i],
+                )?;
+            }
+
+            deposit_state.counter += 1;
+            deposit_state.total_yield_spent = deposit_state
+                .total_yield_spent
+                .checked_add(spent)
+                .ok_or(ErrorCode::MathOverflow)?;
+            deposit_state.total_dca_received = deposit_state
+                .total_dca_received
+                .checked_add(received)
+                .ok_or(ErrorCode::MathOverflow)?;
+            deposit_state.exit(ctx.program_id)?;
+
+            let mut history: Account<'info, ExecutionHistory> = Account::try_from(&group[4])?;
+            if history.deposit != *group[0].key {
+                return Err(ErrorCode::InvalidBatch.into());
+            }
+            history.push(ExecutionRecord {
+                timestamp: now,
+                amount_spent: spent,
+                amount_received: received,
+                average_price: average_price(spent, received),
+            });
+            history.exit(ctx.program_id)?;
+
+            emit!(DcaExecuted {
+                deposit: *group[0].key,
+                yield_spent: spent,
+                amount_received: received,
+                timestamp: now,
+            });
+        }
+
+        ctx.accounts.keeper_stats.record_run(now);
+
+        Ok(())
+    }
+
+    /// Creates a pooled vault DCA-ing the yield of a reserve into dca_mint on a fixed schedule.
+    /// Depositors share one reserve position and one Serum order per run
+    pub fn init_vault(
+        ctx: Context<InitVault>,
+        schedule: DcaSchedule,
+        bumps: VaultBumps,
+    ) -> ProgramResult {
+        if !ctx.accounts.config.is_whitelisted(ctx.accounts.reserve.key) {
+            return Err(ErrorCode::ReserveNotWhitelisted.into());
+        }
+        validate_reserve_mints(
+            &ctx.accounts.reserve,
+            ctx.accounts.liquidity_mint.to_account_info().key,
+            ctx.accounts.reserve_collateral_mint.to_account_info().key,
+        )?;
+
+        let vault = &mut ctx.accounts.vault;
+        vault.reserve_account = *ctx.accounts.reserve.key;
+        vault.liquidity_mint = *ctx.accounts.liquidity_mint.to_account_info().key;
+        vault.dca_mint = *ctx.accounts.dca_mint.to_account_info().key;
+        vault.schedule = schedule;
+        vault.liquidity_account = *ctx.accounts.liquidity_account.to_account_info().key;
+        vault.collateral_account = *ctx.accounts.collateral_account.to_account_info().key;
+        vault.dca_account = *ctx.accounts.dca_account.to_account_info().key;
+        vault.share_mint = *ctx.accounts.share_mint.key;
+        vault.ooa = None;
+        vault.bumps = bumps;
+
+        let vault_key = *ctx.accounts.vault.to_account_info().key;
+        let share_mint_seeds = &[
+            b"vault_shares".as_ref(),
+            vault_key.as_ref(),
+            &[bumps.share_mint],
+        ];
+        create_share_mint(
+            &ctx.accounts.payer,
+            &ctx.accounts.share_mint,
+            &ctx.accounts.vault.to_account_info(),
+            ctx.accounts.liquidity_mint.decimals,
+            &ctx.accounts.rent.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.token_program,
+            share_mint_seeds,
+        )
+    }
+
+    /// Opens the account tracking a user's shares and accrued DCA tokens in a vault.
+    /// The owner's share token account is frozen by the vault from here on
+    pub fn open_vault_position(ctx: Context<OpenVaultPosition>, bump: u8) -> ProgramResult {
+        let position = &mut ctx.accounts.position;
+        position.vault = *ctx.accounts.vault.to_account_info().key;
+        position.owner = *ctx.accounts.owner.key;
+        position.share_account = *ctx.accounts.share_account.to_account_info().key;
+        position.bump = bump;
+
+        let vault_seeds = ctx.accounts.vault.authority_seeds();
+        let vault_seed_refs = seed_slices(&vault_seeds);
+        set_shares_frozen(
+            true,
+            &ctx.accounts.share_account.to_account_info(),
+            &ctx.accounts.share_mint,
+            &ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.token_program,
+            &[&vault_seed_refs[..]],
+        )
+    }
+
+    /// Deposits liquidity into a vault's reserve position and mints vault shares in return
+    pub fn vault_deposit(ctx: Context<VaultDeposit>, liquidity_amount: u64) -> ProgramResult {
+        fund_liquidity(
+            &ctx.accounts.owner,
+            &ctx.accounts.user_liquidity,
+            &ctx.accounts.liquidity_account.to_account_info(),
+            &ctx.accounts.vault.liquidity_mint,
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.token_program,
+            liquidity_amount,
+        )?;
+
+        let vault_seeds = ctx.accounts.vault.authority_seeds();
+        let vault_seed_refs = seed_slices(&vault_seeds);
+        let vault_signer = &[&vault_seed_refs[..]];
+        let vault_info = ctx.accounts.vault.to_account_info();
+
+        let collateral_before =
+            token::accessor::amount(&ctx.accounts.collateral_account.to_account_info())?;
+        ctx.accounts.lending.deposit(
+            &ctx.accounts.liquidity_account.to_account_info(),
+            &ctx.accounts.collateral_account.to_account_info(),
+            &vault_info,
+            &ctx.accounts.clock.to_account_info(),
+            &ctx.accounts.token_program,
+            vault_signer,
+            liquidity_amount,
+        )?;
+
+        let collateral_amount =
+            token::accessor::amount(&ctx.accounts.collateral_account.to_account_info())?;
+        let shares = vault_shares_for(
+            collateral_amount
+                .checked_sub(collateral_before)
+                .ok_or(ErrorCode::MathOverflow)?,
+            collateral_before,
+            ctx.accounts.vault.total_shares,
+        )?;
+
+        // Shares stay frozen outside of vault instructions
+        let share_account = ctx.accounts.share_account.to_account_info();
+        let share_mint = ctx.accounts.share_mint.to_account_info();
+        set_shares_frozen(
+            false,
+            &share_account,
+            &share_mint,
+            &vault_info,
+            &ctx.accounts.token_program,
+            vault_signer,
+        )?;
+        let mint_accounts = token::MintTo {
+            mint: share_mint.clone(),
+            to: share_account.clone(),
+            authority: vault_info.clone(),
+        };
+        token::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.clone(),
+                mint_accounts,
+                vault_signer,
+            ),
+            shares,
+        )?;
+        set_shares_frozen(
+            true,
+            &share_account,
+            &share_mint,
+            &vault_info,
+            &ctx.accounts.token_program,
+            vault_signer,
+        )?;
+
+        let dca_per_share = ctx.accounts.vault.dca_per_share;
+
+        let position = &mut ctx.accounts.position;
+        position.accrue(dca_per_share)?;
+        position.shares = position
+            .shares
+            .checked_add(shares)
+            .ok_or(ErrorCode::MathOverflow)?;
+        position.checkpoint(dca_per_share);
+
+        let vault = &mut ctx.accounts.vault;
+        vault.total_shares = vault
+            .total_shares
+            .checked_add(shares)
+            .ok_or(ErrorCode::MathOverflow)?;
+        vault.liquidity_amount = vault
+            .liquidity_amount
+            .checked_add(liquidity_amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        vault.collateral_amount = collateral_amount;
+
+        Ok(())
+    }
+
+    /// Privileged instruction harvesting a vault's aggregate yield and swapping it for dca_mint
+    /// in a single Serum order. Proceeds stay in the vault until holders claim them
+    #[access_control(validate_keeper(&ctx.accounts.keeper_registry, &ctx.accounts.user_authority))]
+    pub fn run_vault_strategy<'info>(
+        ctx: Context<'_, '_, '_, 'info, RunVaultStrategy<'info>>,
+        side: Side,
+        min_expected_swap_amount: u64,
+        // ooa is only supplied to set the ooa on the vault during the first DCA purchase
+        ooa: Option<Pubkey>,
+    ) -> ProgramResult {
+        if ctx.accounts.vault.total_shares == 0 {
+            return Err(ErrorCode::VaultEmpty.into());
+        }
+        let now = Clock::from_account_info(&ctx.accounts.clock)?.unix_timestamp;
+        let schedule = ctx.accounts.vault.schedule;
+        advance_schedule(&mut ctx.accounts.vault.last_run_at, schedule, now)?;
+
+        let (_, amount_to_redeem) = harvestable_yield(
+            &ctx.accounts.lending.reserve,
+            ctx.accounts.vault.collateral_amount,
+            ctx.accounts.vault.liquidity_amount,
+            0,
+            0,
+            u64::MAX,
+        )?;
+        if amount_to_redeem == 0 {
+            return Err(ErrorCode::NoYieldToSpend.into());
+        }
+
+        let vault_seeds = ctx.accounts.vault.authority_seeds();
+        let vault_seed_refs = seed_slices(&vault_seeds);
+        let vault_signer = &[&vault_seed_refs[..]];
+        let vault_info = ctx.accounts.vault.to_account_info();
+
+        // Harvest the pool's aggregate yield
+        let liquidity_before =
+            token::accessor::amount(&ctx.accounts.liquidity_account.to_account_info())?;
+        ctx.accounts.lending.redeem(
+            &ctx.accounts.collateral_account.to_account_info(),
+            &ctx.accounts.liquidity_account.to_account_info(),
+            &vault_info,
+            &ctx.accounts.clock,
+            &ctx.accounts.token_program_id,
+            vault_signer,
+            amount_to_redeem,
+        )?;
+        let redeemed_amount =
+            token::accessor::amount(&ctx.accounts.liquidity_account.to_account_info())?
+                .checked_sub(liquidity_before)
+                .ok_or(ErrorCode::MathOverflow)?;
+        let fee = charge_fee(
+            &ctx.accounts.config,
+            redeemed_amount,
+            &ctx.accounts.liquidity_account.to_account_info(),
+            &ctx.accounts.fee_account.to_account_info(),
+            &vault_info,
+            &ctx.accounts.token_program_id,
+            vault_signer,
+        )?;
+        // Only this run's harvest is spent, whatever else sits in the liquidity account
+        let spend_amount = redeemed_amount
+            .checked_sub(fee)
+            .ok_or(ErrorCode::MathOverflow)?;
+        if spend_amount == 0 {
+            return Err(ErrorCode::NoYieldToSpend.into());
+        }
+
+        let from_token = ctx.accounts.liquidity_account.to_account_info();
+        let to_token = ctx.accounts.dca_account.to_account_info();
+        let (coin_wallet, pc_wallet) = match side {
+            Side::Bid => (&to_token, &from_token),
+            Side::Ask => (&from_token, &to_token),
+        };
+
+        // Token balances before the trade.
+        let from_amount_before = token::accessor::amount(&from_token)?;
+        let to_amount_before = token::accessor::amount(&to_token)?;
+
+        // Swap everything harvested in one order on behalf of all holders
+        let orderbook = OrderbookClient {
+            market: ctx.accounts.market.clone(),
+            authority: vault_info.clone(),
+            authority_seeds: vault_seeds.clone(),
+            dex_program: ctx.accounts.dex_program.clone(),
+            token_program: ctx.accounts.token_program_id.clone(),
+            rent: ctx.accounts.rent.clone(),
+        };
+        match side {
+            Side::Bid => orderbook.buy(spend_amount, None)?,
+            Side::Ask => orderbook.sell(spend_amount, None)?,
+        }
+        orderbook.settle(None, coin_wallet, pc_wallet)?;
+
+        // Token balances after the trade.
+        let from_amount_after = token::accessor::amount(&from_token)?;
+        let to_amount_after = token::accessor::amount(&to_token)?;
+
+        //  Calculate the delta, i.e. the amount swapped.
+        let from_amount = from_amount_before
+            .checked_sub(from_amount_after)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let to_amount = to_amount_after
+            .checked_sub(to_amount_before)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        // Run safety checks on serum swap
+        apply_risk_checks(DidSwap {
+            authority: *vault_info.key,
+            given_amount: spend_amount,
+            min_expected_swap_amount,
+            from_amount,
+            to_amount,
+            spill_amount: 0,
+            from_mint: token::accessor::mint(&from_token)?,
+            to_mint: token::accessor::mint(&to_token)?,
+            quote_mint: token::accessor::mint(pc_wallet)?,
+        })?;
+
+        let collateral_amount =
+            token::accessor::amount(&ctx.accounts.collateral_account.to_account_info())?;
+        let vault = &mut ctx.accounts.vault;
+        vault.dca_per_share = vault
+            .dca_per_share
+            .checked_add((to_amount as u128) * SHARE_PRECISION / (vault.total_shares as u128))
+            .ok_or(ErrorCode::MathOverflow)?;
+        vault.collateral_amount = collateral_amount;
+        vault.counter += 1;
+        if ooa != None {
+            vault.ooa = ooa;
+        }
+
+        ctx.accounts.keeper_stats.record_run(now);
+
+        Ok(())
+    }
+
+    /// Pays out the DCA tokens a vault position accrued so far
+    pub fn claim_vault(ctx: Context<ClaimVault>) -> ProgramResult {
+        let dca_per_share = ctx.accounts.vault.dca_per_share;
+        let position = &mut ctx.accounts.position;
+        position.accrue(dca_per_share)?;
+        let amount = position.pending;
+        position.pending = 0;
+
+        let vault_seeds = ctx.accounts.vault.authority_seeds();
+        let vault_seed_refs = seed_slices(&vault_seeds);
+        let vault_signer = &[&vault_seed_refs[..]];
+
+        let transfer_accounts = token::Transfer {
+            from: ctx.accounts.dca_account.to_account_info(),
+            to: ctx.accounts.recipient.to_account_info(),
+            authority: ctx.accounts.vault.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.clone(),
+                transfer_accounts,
+                vault_signer,
+            ),
+            amount,
+        )
+    }
+
+    /// Burns vault shares for their pro rata part of the vault's reserve position, yield not
+    /// harvested yet included. Accrued DCA tokens stay claimable
+    pub fn vault_withdraw(ctx: Context<VaultWithdraw>, shares: u64) -> ProgramResult {
+        if shares > ctx.accounts.position.shares {
+            return Err(ErrorCode::InsufficientShares.into());
+        }
+
+        let vault = &ctx.accounts.vault;
+        let collateral_amount = mul_div(shares, vault.collateral_amount, vault.total_shares)?;
+        let liquidity_amount = mul_div(shares, vault.liquidity_amount, vault.total_shares)?;
+
+        let vault_seeds = ctx.accounts.vault.authority_seeds();
+        let vault_seed_refs = seed_slices(&vault_seeds);
+        let vault_signer = &[&vault_seed_refs[..]];
+        let vault_info = ctx.accounts.vault.to_account_info();
+
+        // Burn the shares being redeemed
+        let share_account = ctx.accounts.share_account.to_account_info();
+        let share_mint = ctx.accounts.share_mint.to_account_info();
+        set_shares_frozen(
+            false,
+            &share_account,
+            &share_mint,
+            &vault_info,
+            &ctx.accounts.token_program,
+            vault_signer,
+        )?;
+        let burn_accounts = token::Burn {
+            mint: share_mint.clone(),
+            to: share_account.clone(),
+            authority: ctx.accounts.owner.clone(),
+        };
+        token::burn(
+            CpiContext::new(ctx.accounts.token_program.clone(), burn_accounts),
+            shares,
+        )?;
+        set_shares_frozen(
+            true,
+            &share_account,
+            &share_mint,
+            &vault_info,
+            &ctx.accounts.token_program,
+            vault_signer,
+        )?;
+
+        let liquidity_before =
+            token::accessor::amount(&ctx.accounts.liquidity_account.to_account_info())?;
+        ctx.accounts.lending.redeem(
+            &ctx.accounts.collateral_account.to_account_info(),
+            &ctx.accounts.liquidity_account.to_account_info(),
+            &vault_info,
+            &ctx.accounts.clock.to_account_info(),
+            &ctx.accounts.token_program,
+            vault_signer,
+            collateral_amount,
+        )?;
+        let redeemed = token::accessor::amount(&ctx.accounts.liquidity_account.to_account_info())?
+            .checked_sub(liquidity_before)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        let transfer_accounts = token::Transfer {
+            from: ctx.accounts.liquidity_account.to_account_info(),
+            to: ctx.accounts.user_liquidity.to_account_info(),
+            authority: vault_info.clone(),
+        };
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.clone(),
+                transfer_accounts,
+                vault_signer,
+            ),
+            redeemed,
+        )?;
+
+        let collateral_left =
+            token::accessor::amount(&ctx.accounts.collateral_account.to_account_info())?;
+        let dca_per_share = ctx.accounts.vault.dca_per_share;
+
+        let position = &mut ctx.accounts.position;
+        position.accrue(dca_per_share)?;
+        position.shares = position
+            .shares
+            .checked_sub(shares)
+            .ok_or(ErrorCode::MathOverflow)?;
+        position.checkpoint(dca_per_share);
+
+        let vault = &mut ctx.accounts.vault;
+        vault.total_shares = vault
+            .total_shares
+            .checked_sub(shares)
+            .ok_or(ErrorCode::MathOverflow)?;
+        vault.liquidity_amount = vault
+            .liquidity_amount
+            .checked_sub(liquidity_amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        vault.collateral_amount = collateral_left;
+
+        Ok(())
+    }
+
+    /// Moves vault shares to another position of the same vault, settling both positions'
+    /// accrual first so DCA tokens bought before the move stay with the sender
+    pub fn transfer_vault_shares(ctx: Context<TransferVaultShares>, shares: u64) -> ProgramResult {
+        if shares > ctx.accounts.position.shares {
+            return Err(ErrorCode::InsufficientShares.into());
+        }
+
+        let vault_seeds = ctx.accounts.vault.authority_seeds();
+        let vault_seed_refs = seed_slices(&vault_seeds);
+        let vault_signer = &[&vault_seed_refs[..]];
+        let vault_info = ctx.accounts.vault.to_account_info();
+
+        let from = ctx.accounts.share_account.to_account_info();
+        let to = ctx.accounts.recipient_share_account.to_account_info();
+        for share_account in [&from, &to].iter() {
+            set_shares_frozen(
+                false,
+                share_account,
+                &ctx.accounts.share_mint,
+                &vault_info,
+                &ctx.accounts.token_program,
+                vault_signer,
+            )?;
+        }
+        let transfer_accounts = token::Transfer {
+            from: from.clone(),
+            to: to.clone(),
+            authority: ctx.accounts.owner.clone(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.clone(), transfer_accounts),
+            shares,
+        )?;
+        for share_account in [&from, &to].iter() {
+            set_shares_frozen(
+                true,
+                share_account,
+                &ctx.accounts.share_mint,
+                &vault_info,
+                &ctx.accounts.token_program,
+                vault_signer,
+            )?;
+        }
+
+        let dca_per_share = ctx.accounts.vault.dca_per_share;
+        ctx.accounts.position.transfer_shares(
+            &mut ctx.accounts.recipient_position,
+            shares,
+            dca_per_share,
+        )
+    }
+
+    /// Opens a reverse DCA: `asset_amount` of a held asset is sold off `sell_bps` of the
+    /// remaining balance at a time, and the proceeds are deposited into `reserve` to earn yield
+    pub fn open_reverse_dca(
+        ctx: Context<OpenReverseDca>,
+        asset_amount: u64,
+        sell_bps: u16,
+        schedule: DcaSchedule,
+        index: u64,
+        bumps: ReverseDcaBumps,
+    ) -> ProgramResult {
+        if sell_bps == 0 || sell_bps as u64 > BPS_DENOMINATOR {
+            return Err(ErrorCode::InvalidBasisPoints.into());
+        }
+        if !ctx.accounts.config.is_whitelisted(ctx.accounts.reserve.key) {
+            return Err(ErrorCode::ReserveNotWhitelisted.into());
+        }
+        validate_reserve_mints(
+            &ctx.accounts.reserve,
+            ctx.accounts.liquidity_mint.to_account_info().key,
+            ctx.accounts.reserve_collateral_mint.to_account_info().key,
+        )?;
+
+        fund_liquidity(
+            &ctx.accounts.user_authority,
+            &ctx.accounts.user_asset,
+            &ctx.accounts.asset_account.to_account_info(),
+            ctx.accounts.asset_mint.to_account_info().key,
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.token_program,
+            asset_amount,
+        )?;
+
+        let clock = &ctx.accounts.clock;
+        let reverse_state = &mut ctx.accounts.reverse_state;
+        reverse_state.user_authority = *ctx.accounts.user_authority.key;
+        reverse_state.asset_mint = *ctx.accounts.asset_mint.to_account_info().key;
+        reverse_state.asset_account = *ctx.accounts.asset_account.to_account_info().key;
+        reverse_state.sell_bps = sell_bps;
+        reverse_state.schedule = schedule;
+        reverse_state.reserve_account = *ctx.accounts.reserve.key;
+        reverse_state.liquidity_mint = *ctx.accounts.liquidity_mint.to_account_info().key;
+        reverse_state.liquidity_account = *ctx.accounts.liquidity_account.to_account_info().key;
+        reverse_state.collateral_account = *ctx.accounts.collateral_account.to_account_info().key;
+        reverse_state.ooa = None;
+        reverse_state.created_at = clock.unix_timestamp;
+        reverse_state.index = index;
+        reverse_state.bumps = bumps;
+
+        Ok(())
+    }
+
+    /// Privileged instruction selling the next fraction of a reverse DCA's asset and
+    /// depositing the proceeds into its reserve
+    #[access_control(validate_keeper(&ctx.accounts.keeper_registry, &ctx.accounts.user_authority))]
+    pub fn run_reverse_dca<'info>(
+        ctx: Context<'_, '_, '_, 'info, RunReverseDca<'info>>,
+        side: Side,
+        min_expected_swap_amount: u64,
+        // ooa is only supplied to set the ooa on reverse_state during the first sale
+        ooa: Option<Pubkey>,
+    ) -> ProgramResult {
+        // At most one sale per schedule interval
+        let now = Clock::from_account_info(&ctx.accounts.clock)?.unix_timestamp;
+        let schedule = ctx.accounts.reverse_state.schedule;
+        advance_schedule(&mut ctx.accounts.reverse_state.last_run_at, schedule, now)?;
+
+        let asset = ctx.accounts.asset_account.to_account_info();
+        let liquidity = ctx.accounts.market.destination_liquidity.to_account_info();
+        let sell_amount = mul_div(
+            token::accessor::amount(&asset)?,
+            ctx.accounts.reverse_state.sell_bps as u64,
+            BPS_DENOMINATOR,
+        )?;
+
+        // The asset is always sold and the reserve liquidity always received, the side only
+        // decides which of the two is the market's base currency
+        let (coin_wallet, pc_wallet) = match side {
+            Side::Bid => (&liquidity, &asset),
+            Side::Ask => (&asset, &liquidity),
+        };
+
+        // Token balances before the trade.
+        let from_amount_before = token::accessor::amount(&asset)?;
+        let to_amount_before = token::accessor::amount(&liquidity)?;
+
+        let state_seeds = ctx.accounts.reverse_state.authority_seeds();
+        let state_seed_refs = seed_slices(&state_seeds);
+        let state_signer = &[&state_seed_refs[..]];
+        let state_info = ctx.accounts.reverse_state.to_account_info();
+
+        let orderbook = OrderbookClient {
+            market: ctx.accounts.market.clone(),
+            authority: state_info.clone(),
+            authority_seeds: state_seeds.clone(),
+            dex_program: ctx.accounts.dex_program.clone(),
+            token_program: ctx.accounts.token_program_id.clone(),
+            rent: ctx.accounts.rent.clone(),
+        };
+        match side {
+            Side::Bid => orderbook.buy(sell_amount, None)?,
+            Side::Ask => orderbook.sell(sell_amount, None)?,
+        }
+        orderbook.settle(None, coin_wallet, pc_wallet)?;
+
+        // Token balances after the trade.
+        let from_amount_after = token::accessor::amount(&asset)?;
+        let to_amount_after = token::accessor::amount(&liquidity)?;
+
+        //  Calculate the delta, i.e. the amount swapped.
+        let from_amount = from_amount_before
+            .checked_sub(from_amount_after)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let to_amount = to_amount_after
+            .checked_sub(to_amount_before)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        // Run safety checks on serum swap
+        apply_risk_checks(DidSwap {
+            authority: *state_info.key,
+            given_amount: sell_amount,
+            min_expected_swap_amount,
+            from_amount,
+            to_amount,
+            spill_amount: 0,
+            from_mint: ctx.accounts.reverse_state.asset_mint,
+            to_mint: ctx.accounts.reverse_state.liquidity_mint,
+            quote_mint: token::accessor::mint(pc_wallet)?,
+        })?;
+
+        // Put the proceeds to work
+        ctx.accounts.lending.deposit(
+            &liquidity,
+            &ctx.accounts.collateral_account.to_account_info(),
+            &state_info,
+            &ctx.accounts.clock,
+            &ctx.accounts.token_program_id,
+            state_signer,
+            to_amount,
+        )?;
+
+        let collateral_amount =
+            token::accessor::amount(&ctx.accounts.collateral_account.to_account_info())?;
+        let reverse_state = &mut ctx.accounts.reverse_state;
+        reverse_state.counter += 1;
+        reverse_state.total_sold = reverse_state
+            .total_sold
+            .checked_add(from_amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        reverse_state.liquidity_amount = reverse_state
+            .liquidity_amount
+            .checked_add(to_amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        reverse_state.collateral_amount = collateral_amount;
+        if ooa != None {
+            reverse_state.ooa = ooa;
+        }
+
+        ctx.accounts.keeper_stats.record_run(now);
+
+        Ok(())
+    }
+
+    /// Closes a reverse DCA, returning the unsold asset and redeeming the deposited
+    /// proceeds back to the owner
+    pub fn close_reverse_dca(ctx: Context<CloseReverseDca>) -> ProgramResult {
+        let state_seeds = ctx.accounts.reverse_state.authority_seeds();
+        let state_seed_refs = seed_slices(&state_seeds);
+        let state_signer = &[&state_seed_refs[..]];
+        let state_info = ctx.accounts.reverse_state.to_account_info();
+
+        let collateral_amount =
+            token::accessor::amount(&ctx.accounts.collateral_account.to_account_info())?;
+        if collateral_amount > 0 {
+            ctx.accounts.lending.redeem(
+                &ctx.accounts.collateral_account.to_account_info(),
+                &ctx.accounts.liquidity_account.to_account_info(),
+                &state_info,
+                &ctx.accounts.clock,
+                &ctx.accounts.token_program_id,
+                state_signer,
+                collateral_amount,
+            )?;
+        }
+
+        // Pay out what's left, native SOL is unwrapped into the owner when its account closes
+        for (account, mint, recipient) in &[
+            (
+                ctx.accounts.asset_account.to_account_info(),
+                ctx.accounts.reverse_state.asset_mint,
+                &ctx.accounts.asset_recipient,
+            ),
+            (
+                ctx.accounts.liquidity_account.to_account_info(),
+                ctx.accounts.reverse_state.liquidity_mint,
+                &ctx.accounts.liquidity_recipient,
+            ),
+        ] {
+            if *mint == native_mint::ID {
+                continue;
+            }
+            if token::accessor::authority(recipient)? != *ctx.accounts.user_authority.key {
+                return Err(ErrorCode::InvalidRecipient.into());
+            }
+            let transfer_accounts = token::Transfer {
+                from: account.clone(),
+                to: (*recipient).clone(),
+                authority: state_info.clone(),
+            };
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program_id.clone(),
+                    transfer_accounts,
+                    state_signer,
+                ),
+                token::accessor::amount(account)?,
+            )?;
+        }
+
+        for account in &[
+            ctx.accounts.asset_account.to_account_info(),
+            ctx.accounts.liquidity_account.to_account_info(),
+            ctx.accounts.collateral_account.to_account_info(),
+        ] {
+            let close_accounts = token::CloseAccount {
+                account: account.clone(),
+                destination: ctx.accounts.user_authority.clone(),
+                authority: state_info.clone(),
+            };
+            let close_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program_id.clone(),
+                close_accounts,
+                state_signer,
+            );
+            token::close_account(close_ctx)?;
+        }
+
+        Ok(())
+    }
+
+    /// Creates the protocol config. Bootstrapped by the fee_recipient key, `admin` can then be
+    /// anything able to sign, e.g. an SPL governance or serum multisig PDA
+    pub fn init_config(
+        ctx: Context<InitConfig>,
+        bump: u8,
+        admin: Pubkey,
+        fee_bps: u16,
+        fee_recipient: Pubkey,
+        timelock_delay: i64,
+    ) -> ProgramResult {
+        if fee_bps as u64 > BPS_DENOMINATOR {
+            return Err(ErrorCode::InvalidBasisPoints.into());
+        }
+        check_timelock_delay(timelock_delay)?;
+        let config = &mut ctx.accounts.config;
+        config.admin = admin;
+        config.fee_bps = fee_bps;
+        config.fee_recipient = fee_recipient;
+        config.timelock_delay = timelock_delay;
+        config.queued_action = AdminAction::None;
+        config.bump = bump;
+        Ok(())
+    }
+
+    /// Queues a change of protocol parameters, executable once the timelock delay passed.
+    /// Replaces any action already queued
+    pub fn queue_admin_action(ctx: Context<AdminConfig>, action: AdminAction) -> ProgramResult {
+        match action {
+            AdminAction::SetFee { fee_bps, .. } if fee_bps as u64 > BPS_DENOMINATOR => {
+                return Err(ErrorCode::InvalidBasisPoints.into());
+            }
+            AdminAction::SetTimelockDelay(delay) => check_timelock_delay(delay)?,
+            // An empty whitelist accepts every reserve, so the last entry can't be removed
+            AdminAction::RemoveReserve(_) if ctx.accounts.config.whitelist_len <= 1 => {
+                return Err(ErrorCode::InvalidWhitelistUpdate.into());
+            }
+            _ => {}
+        }
+        let now = ctx.accounts.clock.unix_timestamp;
+        let config = &mut ctx.accounts.config;
+        config.queued_eta = now
+            .checked_add(config.timelock_delay)
+            .ok_or(ErrorCode::MathOverflow)?;
+        config.queued_action = action;
+        Ok(())
+    }
+
+    /// Applies the queued action once its timelock expired
+    pub fn execute_admin_action(ctx: Context<AdminConfig>) -> ProgramResult {
+        let now = ctx.accounts.clock.unix_timestamp;
+        let config = &mut ctx.accounts.config;
+        if let AdminAction::None = config.queued_action {
+            return Err(ErrorCode::NoQueuedAction.into());
+        }
+        if now < config.queued_eta {
+            return Err(ErrorCode::TimelockNotExpired.into());
+        }
+
+        match config.queued_action {
+            AdminAction::None => {}
+            AdminAction::SetAdmin(admin) => config.admin = admin,
+            AdminAction::SetFee {
+                fee_bps,
+                fee_recipient,
+            } => {
+                config.fee_bps = fee_bps;
+                config.fee_recipient = fee_recipient;
+            }
+            AdminAction::SetTimelockDelay(delay) => {
+                check_timelock_delay(delay)?;
+                config.timelock_delay = delay;
+            }
+            AdminAction::AddReserve(reserve) => {
+                let len = config.whitelist_len as usize;
+                if config.reserve_whitelist[..len].contains(&reserve) {
+                    return Err(ErrorCode::InvalidWhitelistUpdate.into());
+                }
+                if len == MAX_WHITELISTED_RESERVES {
+                    return Err(ErrorCode::InvalidWhitelistUpdate.into());
+                }
+                config.reserve_whitelist[len] = reserve;
+                config.whitelist_len += 1;
+            }
+            AdminAction::RemoveReserve(reserve) => {
+                let len = config.whitelist_len as usize;
+                if len <= 1 {
+                    return Err(ErrorCode::InvalidWhitelistUpdate.into());
+                }
+                let index = config.reserve_whitelist[..len]
+                    .iter()
+                    .position(|r| *r == reserve)
+                    .ok_or(ErrorCode::InvalidWhitelistUpdate)?;
+                config.reserve_whitelist[index] = config.reserve_whitelist[len - 1];
+                config.reserve_whitelist[len - 1] = Pubkey::default();
+                config.whitelist_len -= 1;
+            }
+        }
+        config.queued_action = AdminAction::None;
+        config.queued_eta = 0;
+        Ok(())
+    }
+
+    /// Drops the queued action
+    pub fn cancel_admin_action(ctx: Context<AdminConfig>) -> ProgramResult {
+        let config = &mut ctx.accounts.config;
+        config.queued_action = AdminAction::None;
+        config.queued_eta = 0;
+        Ok(())
+    }
+
+    /// Creates the registry of keepers allowed to run strategies
+    pub fn init_keeper_registry(ctx: Context<InitKeeperRegistry>, bump: u8) -> ProgramResult {
+        ctx.accounts.keeper_registry.bump = bump;
+        Ok(())
+    }
+
+    /// Authorizes a keeper key to run strategies and creates its stats account (seeds
+    /// ["keeper", keeper]). Not timelocked so compromised or lost keeper keys can be rotated
+    /// right away
+    pub fn add_keeper(ctx: Context<AddKeeper>, keeper: Pubkey, bump: u8) -> ProgramResult {
+        let registry = &mut ctx.accounts.keeper_registry;
+        let len = registry.keeper_count as usize;
+        if registry.contains(&keeper) || len == MAX_KEEPERS {
+            return Err(ErrorCode::InvalidKeeperUpdate.into());
+        }
+        registry.keepers[len] = keeper;
+        registry.keeper_count += 1;
+
+        let keeper_stats = &mut ctx.accounts.keeper_stats;
+        keeper_stats.keeper = keeper;
+        keeper_stats.bump = bump;
+        Ok(())
+    }
+
+    /// Revokes a keeper key, closing its stats account
+    pub fn remove_keeper(ctx: Context<RemoveKeeper>, keeper: Pubkey) -> ProgramResult {
+        let registry = &mut ctx.accounts.keeper_registry;
+        let len = registry.keeper_count as usize;
+        let index = registry
+            .position(&keeper)
+            .ok_or(ErrorCode::InvalidKeeperUpdate)?;
+        registry.keepers[index] = registry.keepers[len - 1];
+        registry.keepers[len - 1] = Pubkey::default();
+        registry.keeper_count -= 1;
+        Ok(())
+    }
+
+    /// Lets a keeper log a run that failed off chain, failed transactions leave no state
+    pub fn record_keeper_failure(ctx: Context<RecordKeeperFailure>) -> ProgramResult {
+        let keeper_stats = &mut ctx.accounts.keeper_stats;
+        keeper_stats.failures = keeper_stats
+            .failures
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(
+    nonce: u8,
+    liquidity_amount: u64,
+    schedule: DcaSchedule,
+    index: u64,
+    bumps: DepositBumps
+)]
+pub struct Deposit<'info> {
+    // AccountInfo of the account that calls the ix
+    #[account(mut, signer)]
+    pub user_authority: AccountInfo<'info>,
+
+    // Protocol config, for the reserve whitelist
+    #[account(seeds = [b"config".as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    // User's wallet token account the liquidity is pulled from
+    // Ignored for native SOL, which is wrapped straight from user_authority
+    #[account(mut)]
+    pub user_liquidity: AccountInfo<'info>,
+
+    // Token mint of DCA receiving asset
+    pub dca_mint: Account<'info, Mint>,
+
+    // User's ATA of dca_mint, created if it does not exist yet. Must be user_authority
+    // itself when dca_mint is the native mint
+    #[account(mut)]
+    pub dca_recipient: AccountInfo<'info>,
+
+    // Token mint of the reserve liquidity being deposited
+    pub liquidity_mint: Account<'info, Mint>,
+
+    // Solend CPI accounts
+    pub lending: LendingAccounts<'info>,
+    // Transfer authority for source_liquidity and desitnation_collateral accounts
+    // Derived from the deposit so every strategy gets its own collateral owner
+    #[account(seeds = [b"authority".as_ref(), deposit.to_account_info().key.as_ref()], bump = nonce)]
+    pub transfer_authority: AccountInfo<'info>,
+
+    // Deposit state account
+    // Derived from the owner, reserve, DCA mint and a user chosen index so clients can
+    // enumerate a wallet's positions and open several strategies per reserve
+    #[account(
+        init,
+        seeds = [
+            b"deposit".as_ref(),
+            user_authority.key.as_ref(),
+            lending.reserve.key.as_ref(),
+            dca_mint.to_account_info().key.as_ref(),
+            &index.to_le_bytes()
+        ],
+        bump = bumps.deposit,
+        payer = user_authority,
+        space = 8 + DepositState::LEN
+    )]
+    pub deposit: Account<'info, DepositState>,
+
+    // Execution history ring buffer, one per deposit
+    #[account(
+        init,
+        seeds = [b"history".as_ref(), deposit.to_account_info().key.as_ref()],
+        bump = bumps.history,
+        payer = user_authority,
+        space = 8 + ExecutionHistory::LEN
+    )]
+    pub history: Account<'info, ExecutionHistory>,
+
+    // PDA owned token account liquidity passes through on its way into the reserve
+    #[account(
+        init,
+        seeds = [b"liquidity".as_ref(), deposit.to_account_info().key.as_ref()],
+        bump = bumps.liquidity,
+        payer = user_authority,
+        token::mint = liquidity_mint,
+        token::authority = transfer_authority
+    )]
+    pub source_liquidity: Account<'info, TokenAccount>,
+    // PDA owned token account holding the reserve collateral token
+    #[account(
+        init,
+        seeds = [b"collateral".as_ref(), deposit.to_account_info().key.as_ref()],
+        bump = bumps.collateral,
+        payer = user_authority,
+        token::mint = lending.reserve_collateral_mint,
+        token::authority = transfer_authority
+    )]
+    pub destination_collateral: Account<'info, TokenAccount>,
+
+    // Mint of the position NFT representing ownership of the deposit
+    #[account(
+        init,
+        seeds = [b"position".as_ref(), deposit.to_account_info().key.as_ref()],
+        bump = bumps.position,
+        payer = user_authority,
+        mint::decimals = 0,
+        mint::authority = transfer_authority
+    )]
+    pub position_mint: Account<'info, Mint>,
+    // User's ATA of the position mint, created here
+    #[account(mut)]
+    pub position_token_account: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+    // Clock
+    pub clock: Sysvar<'info, Clock>,
+    // Rent
+    pub rent: Sysvar<'info, Rent>,
+    // Token program
+    #[account(constraint = token_program.key == &token::ID)]
+    pub token_program: AccountInfo<'info>,
+    // Associated token account program
+    #[account(constraint = associated_token_program.key == &spl_associated_token_account::ID)]
+    pub associated_token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: u8, liquidity_amount: u64)]
+pub struct AddToDeposit<'info> {
+    // Deposit state being modified
+    #[account(mut)]
+    pub deposit_state: ProgramAccount<'info, DepositState>,
+
+    // Account calling the instruction, must hold the position NFT
+    #[account(mut, signer)]
+    pub user_authority: AccountInfo<'info>,
+
+    // Holder's token account of the position NFT, proves ownership of the deposit
+    #[account(
+        constraint = holds_position(&position_token_account, &deposit_state, user_authority.key)
+    )]
+    pub position_token_account: Account<'info, TokenAccount>,
+
+    // User's wallet token account the liquidity is pulled from
+    // Ignored for native SOL, which is wrapped straight from user_authority
+    #[account(mut)]
+    pub user_liquidity: AccountInfo<'info>,
+
+    // Solend CPI accounts
+    #[account(constraint = *lending.reserve.key == deposit_state.reserve_account)]
+    pub lending: LendingAccounts<'info>,
+    // PDA owned token account created at deposit time
+    #[account(
+        mut,
+        seeds = [
+            b"liquidity".as_ref(),
+            deposit_state.to_account_info().key.as_ref()
+        ],
+        bump = deposit_state.bumps.liquidity
+    )]
+    pub source_liquidity: Account<'info, TokenAccount>,
+    // Token account for reserve collateral token
+    // Make sure account owner is transfer authority PDA
+    #[account(
+        mut,
+        constraint = destination_collateral.owner == *transfer_authority.key,
+        // Destination collateral account should be deterministically derived for consistency - needs to be the same
+        // across all deposits to a deposit state account
+        constraint = *destination_collateral.to_account_info().key == deposit_state.collateral_account_key
+    )]
+    pub destination_collateral: Account<'info, TokenAccount>,
+    // Transfer authority for accounts 1 and 2
+    #[account(seeds = [b"authority".as_ref(), deposit_state.to_account_info().key.as_ref()], bump = nonce)]
+    pub transfer_authority: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+    // Clock
+    pub clock: Sysvar<'info, Clock>,
+    // Token program
+    #[account(constraint = token_program.key == &token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: u8)]
+pub struct RunDcaStrategy<'info> {
+    // Deposite state account being modified
+    #[account(mut)]
+    pub deposit_state: ProgramAccount<'info, DepositState>,
+
+    // Execution history of the deposit, appended to on every run
+    #[account(mut, constraint = history.deposit == *deposit_state.to_account_info().key)]
+    pub history: Account<'info, ExecutionHistory>,
+
+    // Account calling the instruction
+    // Pays for the temporary payout account. Its rent is refunded on split token payouts and
+    // goes to the recipients along with the purchase when native SOL is unwrapped
+    #[account(mut, signer)]
+    pub user_authority: AccountInfo<'info>,
+
+    // Registry of the keepers allowed to run strategies
+    #[account(seeds = [b"keepers".as_ref()], bump = keeper_registry.bump)]
+    pub keeper_registry: Account<'info, KeeperRegistry>,
+    // Run stats of the calling keeper, kept apart so keepers don't write lock each other
+    #[account(
+        mut,
+        seeds = [b"keeper".as_ref(), user_authority.key.as_ref()],
+        bump = keeper_stats.bump
+    )]
+    pub keeper_stats: Account<'info, KeeperStats>,
+
+    // Protocol config, for the fee
+    #[account(seeds = [b"config".as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    // Token account of the liquidity mint owned by the config's fee_recipient
+    #[account(mut, constraint = fee_account.owner == config.fee_recipient)]
+    pub fee_account: Account<'info, TokenAccount>,
+
+    // Solend CPI accounts for RefreshReserve and RedeemReserveCollateral
+    #[account(constraint = *lending.reserve.key == deposit_state.reserve_account)]
+    pub lending: LendingAccounts<'info>,
+
+    // Refresh reserve accounts
+    // Reserve account
+    // pub reserve: AccountInfo<'info>,
+    // // Pyth reserve liquidity oracle
+    // // Must be the pyth price account specified in InitReserve
+    // pub pyth_reserve_liquidity_oracle: AccountInfo<'info>,
+    // // Switchboard Reserve liquidity oracle account
+    // // Must be the switchboard price account specified in InitReserve
+    // pub switchboard_reserve_liquidity_oracle: AccountInfo<'info>,
+
+    // RedeeemReserveCollateral accounts
+    // Source token account for reserve collateral token
+    #[account(
+        mut,
+        constraint = source_collateral.owner == *transfer_authority.key,
+        constraint = *source_collateral.to_account_info().key == deposit_state.collateral_account_key
+    )]
+    pub source_collateral: Account<'info, TokenAccount>,
+    // Token account the Serum proceeds settle into. This is dca_recipient itself, the
+    // temporary wSOL PDA (seeds ["wsol", deposit_state]) when DCA-ing into native SOL, or the
+    // temporary proceeds PDA (seeds ["proceeds", deposit_state]) when splitting proceeds
+    // between recipients, which are then passed through remaining_accounts
+    #[account(mut)]
+    pub serum_recipient: AccountInfo<'info>,
+    // User transfer authority, briefly holds unwrapped SOL on native payouts
+    #[account(mut, seeds = [b"authority".as_ref(), deposit_state.to_account_info().key.as_ref()], bump = nonce)]
+    pub transfer_authority: AccountInfo<'info>,
+
+    // Serum swap accounts
+    market: MarketAccounts<'info>,
+    // Final recipient of the purchased tokens, a system wallet for native SOL
+    #[account(mut, constraint = *dca_recipient.key == deposit_state.dca_recipient)]
+    dca_recipient: AccountInfo<'info>,
+    // Mint of the token being purchased
+    #[account(constraint = *dca_mint.key == deposit_state.dca_mint)]
+    dca_mint: AccountInfo<'info>,
+    // Pyth price account of the deposit, only read by oracle driven strategies
+    pub price_oracle: AccountInfo<'info>,
+    // Programs.
+    #[account(constraint = *dex_program.key == dex::ID)]
+    dex_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+
+    // Misc accounts - Leave at AccountInfo
+    pub clock: AccountInfo<'info>,
+    pub rent: AccountInfo<'info>,
+    #[account(constraint = token_program_id.key == &token::ID)]
+    pub token_program_id: AccountInfo<'info>,
+}
+
+impl<'info> From<&RunDcaStrategy<'info>> for OrderbookClient<'info> {
+    fn from(accounts: &RunDcaStrategy<'info>) -> OrderbookClient<'info> {
+        OrderbookClient {
+            market: accounts.market.clone(),
+            authority: accounts.transfer_authority.clone(),
+            authority_seeds: vec![
+                b"authority".to_vec(),
+                accounts
+                    .deposit_state
+                    .to_account_info()
+                    .key
+                    .to_bytes()
+                    .to_vec(),
+                vec![accounts.deposit_state.nonce],
+            ],
+            // pc_wallet: accounts.dca_recipient.to_account_info().clone(),
+            dex_program: accounts.dex_program.clone(),
+            token_program: accounts.token_program_id.clone(),
+            rent: accounts.rent.clone(),
+        }
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: u8)]
+pub struct CloseAccount<'info> {
+    #[account(mut, close = user_authority)]
+    pub deposit_state: Account<'info, DepositState>,
+
+    #[account(
+        mut,
+        close = user_authority,
+        constraint = history.deposit == *deposit_state.to_account_info().key
+    )]
+    pub history: Account<'info, ExecutionHistory>,
+
+    // Holder of the position NFT
+    #[account(mut, signer)]
+    pub user_authority: AccountInfo<'info>,
+
+    // Holder's token account of the position NFT, proves ownership of the deposit
+    #[account(
+        mut,
+        constraint = holds_position(&position_token_account, &deposit_state, user_authority.key)
+    )]
+    pub position_token_account: Account<'info, TokenAccount>,
+    // Mint of the position NFT, burned on close
+    #[account(mut, constraint = *position_mint.to_account_info().key == deposit_state.position_mint)]
+    pub position_mint: Account<'info, Mint>,
+
+    // User's token account receiving the principal, ignored for native SOL which is
+    // unwrapped into user_authority
+    #[account(mut)]
+    pub liquidity_recipient: AccountInfo<'info>,
+
+    // PDA owned liquidity account the collateral is redeemed into before payout
+    #[account(
+        mut,
+        seeds = [
+            b"liquidity".as_ref(),
+            deposit_state.to_account_info().key.as_ref()
+        ],
+        bump = deposit_state.bumps.liquidity
+    )]
+    pub source_liquidity: Account<'info, TokenAccount>,
+
+    // Solend CPI accounts
+    #[account(constraint = *lending.reserve.key == deposit_state.reserve_account)]
+    pub lending: LendingAccounts<'info>,
+    // RedeeemReserveCollateral accounts
+    // Source token account for reserve collateral token
+    #[account(
+        mut,
+        constraint = source_collateral.owner == *transfer_authority.key,
+        constraint = *source_collateral.to_account_info().key == deposit_state.collateral_account_key
+    )]
+    pub source_collateral: Account<'info, TokenAccount>,
+    // User transfer authority
+    #[account(seeds = [b"authority".as_ref(), deposit_state.to_account_info().key.as_ref()], bump = nonce)]
+    pub transfer_authority: AccountInfo<'info>,
+
+    pub clock: AccountInfo<'info>,
+    pub rent: AccountInfo<'info>,
+    #[account(constraint = token_program_id.key == &token::ID)]
+    pub token_program_id: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateDeposit<'info> {
+    // Deposit state being modified
+    #[account(mut)]
+    pub deposit_state: Account<'info, DepositState>,
+
+    // Holder of the position NFT
+    #[account(signer)]
+    pub user_authority: AccountInfo<'info>,
+
+    // Holder's token account of the position NFT, proves ownership of the deposit
+    #[account(
+        constraint = holds_position(&position_token_account, &deposit_state, user_authority.key)
+    )]
+    pub position_token_account: Account<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: u8)]
+pub struct CloseCompleted<'info> {
+    #[account(mut, close = holder)]
+    pub deposit_state: Account<'info, DepositState>,
+
+    #[account(
+        mut,
+        close = holder,
+        constraint = history.deposit == *deposit_state.to_account_info().key
+    )]
+    pub history: Account<'info, ExecutionHistory>,
+
+    // Current holder's token account of the position NFT
+    #[account(
+        constraint = holds_position(&position_token_account, &deposit_state, &position_token_account.owner)
+    )]
+    pub position_token_account: Account<'info, TokenAccount>,
+    // Holder of the position NFT, receives the principal and account rents
+    #[account(mut, constraint = *holder.key == position_token_account.owner)]
+    pub holder: AccountInfo<'info>,
+    // Holder's ATA of liquidity_mint, ignored for native SOL which is unwrapped into holder
+    #[account(mut)]
+    pub liquidity_recipient: AccountInfo<'info>,
+
+    // PDA owned liquidity account the collateral is redeemed into before payout
+    #[account(
+        mut,
+        seeds = [
+            b"liquidity".as_ref(),
+            deposit_state.to_account_info().key.as_ref()
+        ],
+        bump = deposit_state.bumps.liquidity
+    )]
+    pub source_liquidity: Account<'info, TokenAccount>,
+    // Source token account for reserve collateral token
+    #[account(
+        mut,
+        constraint = *source_collateral.to_account_info().key == deposit_state.collateral_account_key
+    )]
+    pub source_collateral: Account<'info, TokenAccount>,
+    // Solend CPI accounts
+    #[account(constraint = *lending.reserve.key == deposit_state.reserve_account)]
+    pub lending: LendingAccounts<'info>,
+    // User transfer authority
+    #[account(seeds = [b"authority".as_ref(), deposit_state.to_account_info().key.as_ref()], bump = nonce)]
+    pub transfer_authority: AccountInfo<'info>,
+
+    pub clock: AccountInfo<'info>,
+    #[account(constraint = token_program_id.key == &token::ID)]
+    pub token_program_id: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ManageDeposit<'info> {
+    // Deposit state being modified
+    #[account(mut)]
+    pub deposit_state: Account<'info, DepositState>,
+
+    // Holder of the position NFT or the deposit's delegate
+    #[account(signer)]
+    pub user_authority: AccountInfo<'info>,
+
+    // Holder's token account of the position NFT when signing as the owner, ignored when
+    // signing as the delegate
+    pub position_token_account: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetStrategy<'info> {
+    // Deposit state being modified
+    #[account(mut)]
+    pub deposit_state: Account<'info, DepositState>,
+
+    // Holder of the position NFT
+    #[account(signer)]
+    pub user_authority: AccountInfo<'info>,
+
+    // Holder's token account of the position NFT, proves ownership of the deposit
+    #[account(
+        constraint = holds_position(&position_token_account, &deposit_state, user_authority.key)
+    )]
+    pub position_token_account: Account<'info, TokenAccount>,
+
+    // Pyth price account of dca_mint quoted in the liquidity token, ignored for fixed size
+    pub price_oracle: AccountInfo<'info>,
+    // Mints of the deposit, their decimals scale oracle prices
+    #[account(constraint = *liquidity_mint.to_account_info().key == deposit_state.liquidity_mint)]
+    pub liquidity_mint: Account<'info, Mint>,
+    #[account(constraint = *dca_mint.to_account_info().key == deposit_state.dca_mint)]
+    pub dca_mint: Account<'info, Mint>,
+    // Clock, for the oracle's staleness
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateRecipient<'info> {
+    // Deposit state being modified
+    #[account(mut)]
+    pub deposit_state: Account<'info, DepositState>,
+
+    // Holder of the position NFT, pays for the recipient's ATA if it has to be created
+    #[account(mut, signer)]
+    pub user_authority: AccountInfo<'info>,
+
+    // Holder's token account of the position NFT, proves ownership of the deposit
+    #[account(
+        constraint = holds_position(&position_token_account, &deposit_state, user_authority.key)
+    )]
+    pub position_token_account: Account<'info, TokenAccount>,
+
+    // Token mint the deposit DCAs into
+    #[account(constraint = *dca_mint.to_account_info().key == deposit_state.dca_mint)]
+    pub dca_mint: Account<'info, Mint>,
+
+    // Wallet receiving the purchases from now on
+    pub recipient_wallet: AccountInfo<'info>,
+
+    // New recipient, recipient_wallet's ATA of dca_mint, created if it does not exist yet.
+    // Must be recipient_wallet itself when dca_mint is the native mint
+    #[account(mut)]
+    pub dca_recipient: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+    // Rent
+    pub rent: Sysvar<'info, Rent>,
+    // Token program
+    #[account(constraint = token_program.key == &token::ID)]
+    pub token_program: AccountInfo<'info>,
+    // Associated token account program
+    #[account(constraint = associated_token_program.key == &spl_associated_token_account::ID)]
+    pub associated_token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: u8)]
+pub struct ChangeTargetMint<'info> {
+    // Deposit state being modified
+    #[account(mut)]
+    pub deposit_state: Account<'info, DepositState>,
+
+    // Holder of the position NFT, receives the rent of the closed open orders account
+    #[account(mut, signer)]
+    pub user_authority: AccountInfo<'info>,
+
+    // Holder's token account of the position NFT, proves ownership of the deposit
+    #[account(
+        constraint = holds_position(&position_token_account, &deposit_state, user_authority.key)
+    )]
+    pub position_token_account: Account<'info, TokenAccount>,
+
+    // Token mint to DCA into from now on
+    pub dca_mint: Account<'info, Mint>,
+
+    // User's ATA of the new dca_mint, created if it does not exist yet. Must be
+    // user_authority itself when dca_mint is the native mint
+    #[account(mut)]
+    pub dca_recipient: AccountInfo<'info>,
+
+    // Transfer authority owning the deposit's open orders account
+    #[account(seeds = [b"authority".as_ref(), deposit_state.to_account_info().key.as_ref()], bump = nonce)]
+    pub transfer_authority: AccountInfo<'info>,
+
+    // Open orders account of the previous market, ignored if none was opened yet
+    #[account(mut)]
+    pub open_orders: AccountInfo<'info>,
+    // Previous Serum market, ignored if no open orders account was opened yet
+    pub market: AccountInfo<'info>,
+    #[account(constraint = *dex_program.key == dex::ID)]
+    pub dex_program: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+    // Rent
+    pub rent: Sysvar<'info, Rent>,
+    // Token program
+    #[account(constraint = token_program.key == &token::ID)]
+    pub token_program: AccountInfo<'info>,
+    // Associated token account program
+    #[account(constraint = associated_token_program.key == &spl_associated_token_account::ID)]
+    pub associated_token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseLegacyDeposit<'info> {
+    // Depositor recorded in the legacy deposit, gets the liquidity and account rents
+    #[account(mut, signer)]
+    pub user_authority: AccountInfo<'info>,
+
+    // Legacy deposit state, decoded and checked in the instruction
+    #[account(mut)]
+    pub deposit_state: AccountInfo<'info>,
+
+    // Depositor's token account receiving the liquidity
+    #[account(mut, constraint = liquidity_recipient.owner == *user_authority.key)]
+    pub liquidity_recipient: Account<'info, TokenAccount>,
+    // Legacy authority's liquidity account the collateral is redeemed into
+    #[account(mut, constraint = source_liquidity.owner == *transfer_authority.key)]
+    pub source_liquidity: Account<'info, TokenAccount>,
+    // Legacy authority's collateral account
+    #[account(mut)]
+    pub source_collateral: Account<'info, TokenAccount>,
+    // Solend CPI accounts
+    pub lending: LendingAccounts<'info>,
+    // Legacy transfer authority (seeds [user_authority, reserve, nonce])
+    pub transfer_authority: AccountInfo<'info>,
+
+    pub clock: AccountInfo<'info>,
+    #[account(constraint = token_program_id.key == &token::ID)]
+    pub token_program_id: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TransferDeposit<'info> {
+    // Deposit state being transferred
+    #[account(mut)]
+    pub deposit_state: Account<'info, DepositState>,
+
+    // Current holder of the position NFT, pays for the new owner's token account if needed
+    #[account(mut, signer)]
+    pub user_authority: AccountInfo<'info>,
+
+    // Holder's token account of the position NFT, proves ownership of the deposit
+    #[account(
+        mut,
+        constraint = holds_position(&position_token_account, &deposit_state, user_authority.key)
+    )]
+    pub position_token_account: Account<'info, TokenAccount>,
+
+    // Mint of the position NFT
+    #[account(constraint = *position_mint.to_account_info().key == deposit_state.position_mint)]
+    pub position_mint: Account<'info, Mint>,
+
+    // New owner of the deposit, a wallet or a multisig/governance PDA
+    pub new_authority: AccountInfo<'info>,
+    // New owner's ATA of the position mint, created if it does not exist yet
+    #[account(mut)]
+    pub new_position_token_account: AccountInfo<'info>,
+
+    // Token mint the deposit DCAs into
+    #[account(constraint = *dca_mint.to_account_info().key == deposit_state.dca_mint)]
+    pub dca_mint: Account<'info, Mint>,
+    // New owner's ATA of dca_mint, created if it does not exist yet. Must be new_authority
+    // itself when dca_mint is the native mint
+    #[account(mut)]
+    pub dca_recipient: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+    // Rent
+    pub rent: Sysvar<'info, Rent>,
+    // Token program
+    #[account(constraint = token_program.key == &token::ID)]
+    pub token_program: AccountInfo<'info>,
+    // Associated token account program
+    #[account(constraint = associated_token_program.key == &spl_associated_token_account::ID)]
+    pub associated_token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: u8)]
+pub struct RunBasketStrategy<'info> {
+    // Deposit state being run
+    #[account(mut)]
+    pub deposit_state: Account<'info, DepositState>,
+
+    // Execution history of the deposit, appended to by every leg
+    #[account(mut, constraint = history.deposit == *deposit_state.to_account_info().key)]
+    pub history: Account<'info, ExecutionHistory>,
+
+    // Account calling the instruction
+    #[account(signer)]
+    pub user_authority: AccountInfo<'info>,
+
+    // Registry of the keepers allowed to run strategies
+    #[account(seeds = [b"keepers".as_ref()], bump = keeper_registry.bump)]
+    pub keeper_registry: Account<'info, KeeperRegistry>,
+    // Run stats of the calling keeper, kept apart so keepers don't write lock each other
+    #[account(
+        mut,
+        seeds = [b"keeper".as_ref(), user_authority.key.as_ref()],
+        bump = keeper_stats.bump
+    )]
+    pub keeper_stats: Account<'info, KeeperStats>,
+
+    // Protocol config, for the fee
+    #[account(seeds = [b"config".as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    // Token account of the liquidity mint owned by the config's fee_recipient
+    #[account(mut, constraint = fee_account.owner == config.fee_recipient)]
+    pub fee_account: Account<'info, TokenAccount>,
+
+    // Solend CPI accounts
+    #[account(constraint = *lending.reserve.key == deposit_state.reserve_account)]
+    pub lending: LendingAccounts<'info>,
+    // Token account for reserve collateral token
+    #[account(
+        mut,
+        constraint = *source_collateral.to_account_info().key == deposit_state.collateral_account_key
+    )]
+    pub source_collateral: Account<'info, TokenAccount>,
+    // PDA owned token account the yield is redeemed into and every leg is paid from
+    #[account(
+        mut,
+        seeds = [
+            b"liquidity".as_ref(),
+            deposit_state.to_account_info().key.as_ref()
+        ],
+        bump = deposit_state.bumps.liquidity
+    )]
+    pub source_liquidity: Account<'info, TokenAccount>,
+    // Transfer authority for the deposit's accounts and open orders
+    #[account(seeds = [b"authority".as_ref(), deposit_state.to_account_info().key.as_ref()], bump = nonce)]
+    pub transfer_authority: AccountInfo<'info>,
+
+    // Programs.
+    #[account(constraint = *dex_program.key == dex::ID)]
+    dex_program: AccountInfo<'info>,
+
+    // Misc accounts - Leave at AccountInfo
+    pub clock: AccountInfo<'info>,
+    pub rent: AccountInfo<'info>,
+    #[account(constraint = token_program_id.key == &token::ID)]
+    pub token_program_id: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(bump: u8)]
+pub struct RunDcaBatch<'info> {
+    // Account calling the instruction
+    #[account(signer)]
+    pub user_authority: AccountInfo<'info>,
+
+    // Registry of the keepers allowed to run strategies
+    #[account(seeds = [b"keepers".as_ref()], bump = keeper_registry.bump)]
+    pub keeper_registry: Account<'info, KeeperRegistry>,
+    // Run stats of the calling keeper, kept apart so keepers don't write lock each other
+    #[account(
+        mut,
+        seeds = [b"keeper".as_ref(), user_authority.key.as_ref()],
+        bump = keeper_stats.bump
+    )]
+    pub keeper_stats: Account<'info, KeeperStats>,
+
+    // Protocol config, for the fee
+    #[account(seeds = [b"config".as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    // Token account of the liquidity mint owned by the config's fee_recipient
+    #[account(mut, constraint = fee_account.owner == config.fee_recipient)]
+    pub fee_account: Account<'info, TokenAccount>,
+
+    // Program wide PDA placing the batched orders and owning the batch token accounts
+    #[account(seeds = [b"batch".as_ref()], bump = bump)]
+    pub batch_authority: AccountInfo<'info>,
+    // Batch owned token account of dca_mint the Serum proceeds settle into before distribution
+    #[account(mut, constraint = batch_proceeds.owner == *batch_authority.key)]
+    pub batch_proceeds: Account<'info, TokenAccount>,
+
+    // Solend CPI accounts
+    pub lending: LendingAccounts<'info>,
+
+    // Serum swap accounts. destination_liquidity is the batch owned liquidity account
+    // every deposit's yield is redeemed into
+    #[account(
+        constraint = market.destination_liquidity.owner == *batch_authority.key,
+        constraint = *market.order_payer_token_account.key == *market.destination_liquidity.to_account_info().key
+    )]
+    market: MarketAccounts<'info>,
+    // Programs.
+    #[account(constraint = *dex_program.key == dex::ID)]
+    dex_program: AccountInfo<'info>,
+
+    // Misc accounts - Leave at AccountInfo
+    pub clock: AccountInfo<'info>,
+    pub rent: AccountInfo<'info>,
+    #[account(constraint = token_program_id.key == &token::ID)]
+    pub token_program_id: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(schedule: DcaSchedule, bumps: VaultBumps)]
+pub struct InitVault<'info> {
+    // Pays for the vault accounts
+    #[account(mut, signer)]
+    pub payer: AccountInfo<'info>,
+
+    // Protocol config, for the reserve whitelist
+    #[account(seeds = [b"config".as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    // Reserve state account the pooled liquidity is deposited into
+    pub reserve: AccountInfo<'info>,
+    // Token mint of the reserve liquidity
+    pub liquidity_mint: Account<'info, Mint>,
+    // Token mint for reserve collateral token
+    pub reserve_collateral_mint: Account<'info, Mint>,
+    // Token mint the vault DCAs into
+    pub dca_mint: Account<'info, Mint>,
+
+    // One vault per (reserve, dca_mint, schedule)
+    #[account(
+        init,
+        seeds = [
+            b"vault".as_ref(),
+            reserve.key.as_ref(),
+            dca_mint.to_account_info().key.as_ref(),
+            &[schedule as u8]
+        ],
+        bump = bumps.vault,
+        payer = payer,
+        space = 8 + Vault::LEN
+    )]
+    pub vault: Account<'info, Vault>,
+
+    // Vault owned token account liquidity passes through
+    #[account(
+        init,
+        seeds = [b"vault_liquidity".as_ref(), vault.to_account_info().key.as_ref()],
+        bump = bumps.liquidity,
+        payer = payer,
+        token::mint = liquidity_mint,
+        token::authority = vault
+    )]
+    pub liquidity_account: Account<'info, TokenAccount>,
+    // Vault owned token account holding the reserve collateral token
+    #[account(
+        init,
+        seeds = [b"vault_collateral".as_ref(), vault.to_account_info().key.as_ref()],
+        bump = bumps.collateral,
+        payer = payer,
+        token::mint = reserve_collateral_mint,
+        token::authority = vault
+    )]
+    pub collateral_account: Account<'info, TokenAccount>,
+    // Vault owned token account holding purchased DCA tokens until they are claimed
+    #[account(
+        init,
+        seeds = [b"vault_dca".as_ref(), vault.to_account_info().key.as_ref()],
+        bump = bumps.dca,
+        payer = payer,
+        token::mint = dca_mint,
+        token::authority = vault
+    )]
+    pub dca_account: Account<'info, TokenAccount>,
+    // Mint of the vault share tokens, created in the instruction since it needs a freeze
+    // authority
+    #[account(
+        mut,
+        seeds = [b"vault_shares".as_ref(), vault.to_account_info().key.as_ref()],
+        bump = bumps.share_mint
+    )]
+    pub share_mint: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+    // Rent
+    pub rent: Sysvar<'info, Rent>,
+    // Token program
+    #[account(constraint = token_program.key == &token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(bump: u8)]
+pub struct OpenVaultPosition<'info> {
+    pub vault: Account<'info, Vault>,
+    #[account(constraint = *share_mint.key == vault.share_mint)]
+    pub share_mint: AccountInfo<'info>,
+
+    // One position per (vault, owner)
+    #[account(
+        init,
+        seeds = [
+            b"vault_position".as_ref(),
+            vault.to_account_info().key.as_ref(),
+            owner.key.as_ref()
+        ],
+        bump = bump,
+        payer = owner,
+        space = 8 + VaultPosition::LEN
+    )]
+    pub position: Account<'info, VaultPosition>,
+
+    #[account(mut, signer)]
+    pub owner: AccountInfo<'info>,
+    // Owner's empty token account of the share mint, frozen here for the life of the position
+    #[account(
+        mut,
+        constraint = share_account.mint == vault.share_mint,
+        constraint = share_account.owner == *owner.key,
+        constraint = share_account.amount == 0
+    )]
+    pub share_account: Account<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+    // Token program
+    #[account(constraint = token_program.key == &token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(liquidity_amount: u64)]
+pub struct VaultDeposit<'info> {
+    #[account(mut, constraint = vault.reserve_account == *lending.reserve.key)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut, has_one = vault, has_one = owner)]
+    pub position: Account<'info, VaultPosition>,
+
+    #[account(mut, signer)]
+    pub owner: AccountInfo<'info>,
+
+    // Owner's wallet token account the liquidity is pulled from
+    // Ignored for native SOL, which is wrapped straight from owner
+    #[account(mut)]
+    pub user_liquidity: AccountInfo<'info>,
+    // Position's token account receiving the vault shares
+    #[account(mut, constraint = *share_account.to_account_info().key == position.share_account)]
+    pub share_account: Account<'info, TokenAccount>,
+    #[account(mut, constraint = *share_mint.to_account_info().key == vault.share_mint)]
+    pub share_mint: Account<'info, Mint>,
+
+    // Vault owned token accounts
+    #[account(mut, constraint = *liquidity_account.to_account_info().key == vault.liquidity_account)]
+    pub liquidity_account: Account<'info, TokenAccount>,
+    #[account(mut, constraint = *collateral_account.to_account_info().key == vault.collateral_account)]
+    pub collateral_account: Account<'info, TokenAccount>,
+
+    // Solend CPI accounts
+    pub lending: LendingAccounts<'info>,
+
+    pub system_program: Program<'info, System>,
+    // Clock
+    pub clock: Sysvar<'info, Clock>,
+    // Token program
+    #[account(constraint = token_program.key == &token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RunVaultStrategy<'info> {
+    #[account(mut, constraint = vault.reserve_account == *lending.reserve.key)]
+    pub vault: Account<'info, Vault>,
+
+    // Account calling the instruction
+    #[account(signer)]
+    pub user_authority: AccountInfo<'info>,
+
+    // Registry of the keepers allowed to run strategies
+    #[account(seeds = [b"keepers".as_ref()], bump = keeper_registry.bump)]
+    pub keeper_registry: Account<'info, KeeperRegistry>,
+    // Run stats of the calling keeper, kept apart so keepers don't write lock each other
+    #[account(
+        mut,
+        seeds = [b"keeper".as_ref(), user_authority.key.as_ref()],
+        bump = keeper_stats.bump
+    )]
+    pub keeper_stats: Account<'info, KeeperStats>,
+
+    // Protocol config, for the fee
+    #[account(seeds = [b"config".as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    // Token account of the liquidity mint owned by the config's fee_recipient
+    #[account(mut, constraint = fee_account.owner == config.fee_recipient)]
+    pub fee_account: Account<'info, TokenAccount>,
+
+    // Vault owned token accounts
+    #[account(mut, constraint = *collateral_account.to_account_info().key == vault.collateral_account)]
+    pub collateral_account: Account<'info, TokenAccount>,
+    // Harvested yield lands here and pays for the order
+    #[account(
+        mut,
+        constraint = *liquidity_account.to_account_info().key == vault.liquidity_account,
+        constraint = *market.order_payer_token_account.key == vault.liquidity_account
+    )]
+    pub liquidity_account: Account<'info, TokenAccount>,
+    #[account(mut, constraint = *dca_account.to_account_info().key == vault.dca_account)]
+    pub dca_account: Account<'info, TokenAccount>,
+
+    // Solend CPI accounts
+    pub lending: LendingAccounts<'info>,
+
+    // Serum swap accounts
+    market: MarketAccounts<'info>,
+    // Programs.
+    #[account(constraint = *dex_program.key == dex::ID)]
+    dex_program: AccountInfo<'info>,
+
+    // Misc accounts - Leave at AccountInfo
+    pub clock: AccountInfo<'info>,
+    pub rent: AccountInfo<'info>,
+    #[account(constraint = token_program_id.key == &token::ID)]
+    pub token_program_id: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimVault<'info> {
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut, has_one = vault, has_one = owner)]
+    pub position: Account<'info, VaultPosition>,
+
+    #[account(signer)]
+    pub owner: AccountInfo<'info>,
+
+    #[account(mut, constraint = *dca_account.to_account_info().key == vault.dca_account)]
+    pub dca_account: Account<'info, TokenAccount>,
+    // Token account of dca_mint receiving the claim
+    #[account(mut, constraint = recipient.mint == vault.dca_mint)]
+    pub recipient: Account<'info, TokenAccount>,
+
+    // Token program
+    #[account(constraint = token_program.key == &token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct VaultWithdraw<'info> {
+    #[account(mut, constraint = vault.reserve_account == *lending.reserve.key)]
+    pub vault: Account<'info, Vault>,
+
+    #[account(mut, has_one = vault, has_one = owner)]
+    pub position: Account<'info, VaultPosition>,
+
+    #[account(signer)]
+    pub owner: AccountInfo<'info>,
+
+    // Position's token account holding the shares being burned
+    #[account(mut, constraint = *share_account.to_account_info().key == position.share_account)]
+    pub share_account: Account<'info, TokenAccount>,
+    #[account(mut, constraint = *share_mint.to_account_info().key == vault.share_mint)]
+    pub share_mint: Account<'info, Mint>,
+    // Owner's token account receiving the principal
+    #[account(
+        mut,
+        constraint = user_liquidity.mint == vault.liquidity_mint,
+        constraint = user_liquidity.owner == *owner.key
+    )]
+    pub user_liquidity: Account<'info, TokenAccount>,
+
+    // Vault owned token accounts
+    #[account(mut, constraint = *liquidity_account.to_account_info().key == vault.liquidity_account)]
+    pub liquidity_account: Account<'info, TokenAccount>,
+    #[account(mut, constraint = *collateral_account.to_account_info().key == vault.collateral_account)]
+    pub collateral_account: Account<'info, TokenAccount>,
+
+    // Solend CPI accounts
+    pub lending: LendingAccounts<'info>,
+
+    // Clock
+    pub clock: Sysvar<'info, Clock>,
+    // Token program
+    #[account(constraint = token_program.key == &token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TransferVaultShares<'info> {
+    pub vault: Account<'info, Vault>,
+    #[account(constraint = *share_mint.key == vault.share_mint)]
+    pub share_mint: AccountInfo<'info>,
+
+    // Position the shares move out of
+    #[account(mut, has_one = vault, has_one = owner)]
+    pub position: Account<'info, VaultPosition>,
+    #[account(signer)]
+    pub owner: AccountInfo<'info>,
+    #[account(mut, constraint = *share_account.to_account_info().key == position.share_account)]
+    pub share_account: Account<'info, TokenAccount>,
+
+    // Position the shares move into
+    #[account(
+        mut,
+        has_one = vault,
+        constraint = recipient_position.to_account_info().key != position.to_account_info().key
+    )]
+    pub recipient_position: Account<'info, VaultPosition>,
+    #[account(
+        mut,
+        constraint = *recipient_share_account.to_account_info().key == recipient_position.share_account
+    )]
+    pub recipient_share_account: Account<'info, TokenAccount>,
+
+    // Token program
+    #[account(constraint = token_program.key == &token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(asset_amount: u64, sell_bps: u16, schedule: DcaSchedule, index: u64, bumps: ReverseDcaBumps)]
+pub struct OpenReverseDca<'info> {
+    // Owner of the reverse DCA, funds the asset and pays for the accounts
+    #[account(mut, signer)]
+    pub user_authority: AccountInfo<'info>,
+
+    // Protocol config, for the reserve whitelist
+    #[account(seeds = [b"config".as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    // User's wallet token account the asset is pulled from
+    // Ignored for native SOL, which is wrapped straight from user_authority
+    #[account(mut)]
+    pub user_asset: AccountInfo<'info>,
+
+    // Token mint of the asset being sold
+    pub asset_mint: Account<'info, Mint>,
+    // Reserve state account the proceeds are deposited into
+    pub reserve: AccountInfo<'info>,
+    // Token mint of the reserve liquidity, i.e. what the asset is sold for
+    pub liquidity_mint: Account<'info, Mint>,
+    // Token mint for reserve collateral token
+    pub reserve_collateral_mint: Account<'info, Mint>,
+
+    #[account(
+        init,
+        seeds = [
+            b"reverse".as_ref(),
+            user_authority.key.as_ref(),
+            asset_mint.to_account_info().key.as_ref(),
+            index.to_le_bytes().as_ref()
+        ],
+        bump = bumps.state,
+        payer = user_authority,
+        space = 8 + ReverseDcaState::LEN
+    )]
+    pub reverse_state: Account<'info, ReverseDcaState>,
+
+    // State owned token account holding the asset left to sell
+    #[account(
+        init,
+        seeds = [b"reverse_asset".as_ref(), reverse_state.to_account_info().key.as_ref()],
+        bump = bumps.asset,
+        payer = user_authority,
+        token::mint = asset_mint,
+        token::authority = reverse_state
+    )]
+    pub asset_account: Account<'info, TokenAccount>,
+    // State owned token account the sale proceeds settle into
+    #[account(
+        init,
+        seeds = [b"reverse_liquidity".as_ref(), reverse_state.to_account_info().key.as_ref()],
+        bump = bumps.liquidity,
+        payer = user_authority,
+        token::mint = liquidity_mint,
+        token::authority = reverse_state
+    )]
+    pub liquidity_account: Account<'info, TokenAccount>,
+    // State owned token account holding the reserve collateral token
+    #[account(
+        init,
+        seeds = [b"reverse_collateral".as_ref(), reverse_state.to_account_info().key.as_ref()],
+        bump = bumps.collateral,
+        payer = user_authority,
+        token::mint = reserve_collateral_mint,
+        token::authority = reverse_state
+    )]
+    pub collateral_account: Account<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+    // Clock
+    pub clock: Sysvar<'info, Clock>,
+    // Rent
+    pub rent: Sysvar<'info, Rent>,
+    // Token program
+    #[account(constraint = token_program.key == &token::ID)]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RunReverseDca<'info> {
+    #[account(mut, constraint = reverse_state.reserve_account == *lending.reserve.key)]
+    pub reverse_state: Account<'info, ReverseDcaState>,
+
+    // Account calling the instruction
+    #[account(signer)]
+    pub user_authority: AccountInfo<'info>,
+
+    // Registry of the keepers allowed to run strategies
+    #[account(seeds = [b"keepers".as_ref()], bump = keeper_registry.bump)]
+    pub keeper_registry: Account<'info, KeeperRegistry>,
+    // Run stats of the calling keeper, kept apart so keepers don't write lock each other
+    #[account(
+        mut,
+        seeds = [b"keeper".as_ref(), user_authority.key.as_ref()],
+        bump = keeper_stats.bump
+    )]
+    pub keeper_stats: Account<'info, KeeperStats>,
+
+    // State owned token accounts
+    #[account(mut, constraint = *asset_account.to_account_info().key == reverse_state.asset_account)]
+    pub asset_account: Account<'info, TokenAccount>,
+    #[account(mut, constraint = *collateral_account.to_account_info().key == reverse_state.collateral_account)]
+    pub collateral_account: Account<'info, TokenAccount>,
+
+    // Solend CPI accounts
+    pub lending: LendingAccounts<'info>,
+
+    // Serum swap accounts. The asset account pays for the order and proceeds settle into
+    // destination_liquidity, the state's liquidity account
+    #[account(
+        constraint = *market.order_payer_token_account.key == reverse_state.asset_account,
+        constraint = *market.destination_liquidity.to_account_info().key == reverse_state.liquidity_account
+    )]
+    market: MarketAccounts<'info>,
+    // Programs.
+    #[account(constraint = *dex_program.key == dex::ID)]
+    dex_program: AccountInfo<'info>,
+
+    // Misc accounts - Leave at AccountInfo
+    pub clock: AccountInfo<'info>,
+    pub rent: AccountInfo<'info>,
+    #[account(constraint = token_program_id.key == &token::ID)]
+    pub token_program_id: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseReverseDca<'info> {
+    #[account(
+        mut,
+        close = user_authority,
+        has_one = user_authority,
+        constraint = reverse_state.reserve_account == *lending.reserve.key
+    )]
+    pub reverse_state: Account<'info, ReverseDcaState>,
+
+    // Owner of the reverse DCA
+    #[account(mut, signer)]
+    pub user_authority: AccountInfo<'info>,
+
+    // User's token accounts receiving the unsold asset and the redeemed proceeds, ignored
+    // for native SOL which is unwrapped into user_authority
+    #[account(mut)]
+    pub asset_recipient: AccountInfo<'info>,
+    #[account(mut)]
+    pub liquidity_recipient: AccountInfo<'info>,
+
+    // State owned token accounts
+    #[account(mut, constraint = *asset_account.to_account_info().key == reverse_state.asset_account)]
+    pub asset_account: Account<'info, TokenAccount>,
+    #[account(mut, constraint = *liquidity_account.to_account_info().key == reverse_state.liquidity_account)]
+    pub liquidity_account: Account<'info, TokenAccount>,
+    #[account(mut, constraint = *collateral_account.to_account_info().key == reverse_state.collateral_account)]
+    pub collateral_account: Account<'info, TokenAccount>,
+
+    // Solend CPI accounts
+    pub lending: LendingAccounts<'info>,
+
+    pub clock: AccountInfo<'info>,
+    #[account(constraint = token_program_id.key == &token::ID)]
+    pub token_program_id: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(bump: u8)]
+pub struct InitConfig<'info> {
+    #[account(
+        init,
+        seeds = [b"config".as_ref()],
+        bump = bump,
+        payer = payer,
+        space = 8 + Config::LEN
+    )]
+    pub config: Account<'info, Config>,
+
+    // Bootstrap key, see fee_recipient
+    #[account(mut, signer, constraint = *payer.key == fee_recipient::ID)]
+    pub payer: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AdminConfig<'info> {
+    #[account(mut, has_one = admin, seeds = [b"config".as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    // Config admin, signing directly or through its owning program's CPI
+    #[account(signer)]
+    pub admin: AccountInfo<'info>,
+
+    // Clock
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+#[instruction(bump: u8)]
+pub struct InitKeeperRegistry<'info> {
+    #[account(
+        init,
+        seeds = [b"keepers".as_ref()],
+        bump = bump,
+        payer = admin,
+        space = 8 + KeeperRegistry::LEN
+    )]
+    pub keeper_registry: Account<'info, KeeperRegistry>,
+
+    #[account(has_one = admin, seeds = [b"config".as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    // Config admin
+    #[account(mut, signer)]
+    pub admin: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(keeper: Pubkey, bump: u8)]
+pub struct AddKeeper<'info> {
+    #[account(mut, seeds = [b"keepers".as_ref()], bump = keeper_registry.bump)]
+    pub keeper_registry: Account<'info, KeeperRegistry>,
+
+    // Run stats of the new keeper
+    #[account(
+        init,
+        seeds = [b"keeper".as_ref(), keeper.as_ref()],
+        bump = bump,
+        payer = admin,
+        space = 8 + KeeperStats::LEN
+    )]
+    pub keeper_stats: Account<'info, KeeperStats>,
+
+    #[account(has_one = admin, seeds = [b"config".as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    // Config admin
+    #[account(mut, signer)]
+    pub admin: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(keeper: Pubkey)]
+pub struct RemoveKeeper<'info> {
+    #[account(mut, seeds = [b"keepers".as_ref()], bump = keeper_registry.bump)]
+    pub keeper_registry: Account<'info, KeeperRegistry>,
+
+    // Run stats of the keeper being removed
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"keeper".as_ref(), keeper.as_ref()],
+        bump = keeper_stats.bump
+    )]
+    pub keeper_stats: Account<'info, KeeperStats>,
+
+    #[account(has_one = admin, seeds = [b"config".as_ref()], bump = config.bump)]
+    pub config: Account<'info, Config>,
+
+    // Config admin
+    #[account(mut, signer)]
+    pub admin: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RecordKeeperFailure<'info> {
+    // The keeper's own stats account
+    #[account(
+        mut,
+        seeds = [b"keeper".as_ref(), keeper.key.as_ref()],
+        bump = keeper_stats.bump
+    )]
+    pub keeper_stats: Account<'info, KeeperStats>,
+
+    #[account(signer)]
+    pub keeper: AccountInfo<'info>,
+}
+
+#[account]
+#[derive(Default)]
+pub struct DepositState {
+    // Pubkey of the owner, set to the depositor and updated by transfer_deposit. Ownership
+    // checks go through the holder of position_mint
+    pub user_authority: Pubkey,
+    // Mint of the position NFT representing this deposit
+    pub position_mint: Pubkey,
+    // Pubkey of account holding the reserve collateral token
+    // Used for AddToDeposit context struct constraints
+    pub collateral_account_key: Pubkey,
+    // Current amount of liquidity tokens deposited
+    // Update on withdraw or modification
+    pub liquidity_amount: u64,
+    // Current amount of reserve collateral tokens being controlled by PDA
+    // Update on withdraw or modification
+    pub collateral_amount: u64,
+    // DCA schedule for deposit
+    pub schedule: DcaSchedule,
+    // How much principal is spent per run on top of the yield
+    pub principal_mode: PrincipalMode,
+    // Share of the harvested yield compounded into liquidity_amount, in basis points
+    pub compound_bps: u16,
+    // How the size of each purchase is decided
+    pub strategy: DcaStrategy,
+    // Pyth price account of dca_mint in the liquidity token, default when unused
+    pub price_oracle: Pubkey,
+    // Oracle price above which runs are refused, scaled like average_entry_price. 0 when unset
+    pub price_ceiling: u64,
+    // Runs are refused while set
+    pub paused: bool,
+    // Manager allowed to tune schedule, slippage and pause state on the owner's behalf
+    pub delegate: Option<Pubkey>,
+    // Max shortfall of a purchase against the oracle price, in basis points. 0 when unset
+    pub max_slippage_bps: u16,
+    // End conditions of the campaign, 0 when unset
+    pub max_executions: u16,
+    pub end_at: i64,
+    pub target_dca_amount: u64,
+    // Decimals of liquidity_mint and dca_mint, cached for oracle pricing
+    pub liquidity_decimals: u8,
+    pub dca_decimals: u8,
+    // Pubkey of reserve account of pool where liquidity is deposited
+    pub reserve_account: Pubkey,
+    // Token mint of the reserve liquidity
+    pub liquidity_mint: Pubkey,
+    // Token mint of token to run dca strategy on
+    pub dca_mint: Pubkey,
+    // Set this as ATA of signer
+    pub dca_recipient: Pubkey,
+    // Weighted recipients purchases are split across instead of dca_recipient, the first
+    // split_count entries are used
+    pub splits: [RecipientSplit; MAX_RECIPIENTS],
+    pub split_count: u8,
+    // Weighted target mints the yield is spread across instead of dca_mint, the first
+    // basket_len entries are used
+    pub basket: [BasketLeg; MAX_BASKET_LEGS],
+    pub basket_len: u8,
+    // OOA Pubkey
+    pub ooa: Option<Pubkey>,
+
+    // Unix timestamp of deposit
+    pub created_at: i64,
+    // Integer representing the amount of times a DCA has executed
+    pub counter: u16,
+    // Nonce
+    pub nonce: u8,
+    // Cumulative amount of liquidity tokens spent on DCA purchases
+    pub total_yield_spent: u64,
+    // Cumulative amount of DCA tokens received from purchases
+    pub total_dca_received: u64,
+    // User chosen index, part of the DepositState PDA seeds
+    pub index: u64,
+    // Bump seeds of the PDAs created alongside the deposit
+    pub bumps: DepositBumps,
+    // Layout version, DEPOSIT_STATE_VERSION when the deposit was written. Deposits from
+    // before versioning have a different layout altogether, see LegacyDepositState
+    pub version: u8,
+    // Unix timestamp of the last run, 0 before the first one. Runs are refused until a
+    // schedule interval passed since
+    pub last_run_at: i64,
+    // Zeroed space later versions carve new fields out of without a migration
+    pub reserved: [u64; 15],
+}
+
+impl DepositState {
+    pub const LEN: usize = 32 // user_authority
+        + 32 // position_mint
+        + 32 // collateral_account_key
+        + 8 // liquidity_amount
+        + 8 // collateral_amount
+        + 1 // schedule
+        + PrincipalMode::LEN // principal_mode
+        + 2 // compound_bps
+        + DcaStrategy::LEN // strategy
+        + 32 // price_oracle
+        + 8 // price_ceiling
+        + 1 // paused
+        + 33 // delegate
+        + 2 // max_slippage_bps
+        + 2 // max_executions
+        + 8 // end_at
+        + 8 // target_dca_amount
+        + 1 // liquidity_decimals
+        + 1 // dca_decimals
+        + 32 // reserve_account
+        + 32 // liquidity_mint
+        + 32 // dca_mint
+        + 32 // dca_recipient
+        + RecipientSplit::LEN * MAX_RECIPIENTS // splits
+        + 1 // split_count
+        + BasketLeg::LEN * MAX_BASKET_LEGS // basket
+        + 1 // basket_len
+        + 33 // ooa
+        + 8 // created_at
+        + 2 // counter
+        + 1 // nonce
+        + 8 // total_yield_spent
+        + 8 // total_dca_received
+        + 8 // index
+        + DepositBumps::LEN // bumps
+        + 1 // version
+        + 8 // last_run_at
+        + 8 * 15; // reserved
+
+    /// Volume-weighted average entry price across all executions, i.e. liquidity tokens
+    /// paid per DCA token scaled by PRICE_PRECISION. None until something was purchased.
+    pub fn average_entry_price(&self) -> Option<u64> {
+        if self.total_dca_received == 0 {
+            return None;
+        }
+        Some(average_price(
+            self.total_yield_spent,
+            self.total_dca_received,
+        ))
+    }
+
+    /// Whether the deposit met one of its end conditions at unix timestamp `now`
+    pub fn is_completed(&self, now: i64) -> bool {
+        (self.max_executions > 0 && self.counter >= self.max_executions)
+            || (self.end_at > 0 && now >= self.end_at)
+            || (self.target_dca_amount > 0 && self.total_dca_received >= self.target_dca_amount)
+    }
+
+    /// Fails if the deposit can't be run at unix timestamp `now`, i.e. it is paused or met its
+    /// end conditions
+    pub fn check_runnable(&self, now: i64) -> ProgramResult {
+        if self.paused {
+            return Err(ErrorCode::DepositPaused.into());
+        }
+        if self.is_completed(now) {
+            return Err(ErrorCode::StrategyCompleted.into());
+        }
+        Ok(())
+    }
+
+    /// Whether runs read the deposit's price_oracle
+    pub fn uses_oracle(&self) -> bool {
+        self.price_ceiling > 0
+            || self.max_slippage_bps > 0
+            || matches!(self.strategy, DcaStrategy::ValueAveraging { .. })
+    }
+
+    /// Whether runs buy dca_mint, either directly or through a leg of the basket
+    pub fn buys_dca_mint(&self) -> bool {
+        let basket = &self.basket[..self.basket_len as usize];
+        basket.is_empty() || basket.iter().any(|leg| leg.mint == self.dca_mint)
+    }
+
+    /// Fails if a purchase target is set that runs can never reach, i.e. the basket never
+    /// buys dca_mint
+    pub fn check_target_reachable(&self) -> ProgramResult {
+        if self.target_dca_amount > 0 && !self.buys_dca_mint() {
+            return Err(ErrorCode::InvalidBasket.into());
+        }
+        Ok(())
+    }
+
+    /// Moves the value averaging target path forward by `intervals` schedule intervals
+    pub fn advance_value_path(&mut self, intervals: u64) {
+        if let DcaStrategy::ValueAveraging { runs, .. } = &mut self.strategy {
+            *runs = runs.saturating_add(intervals.min(u32::MAX as u64) as u32);
+        }
+    }
+
+    /// Takes the principal spent by a run out of liquidity_amount and adds the yield
+    /// compounded into the reserve to it
+    pub fn settle_principal(&mut self, principal_spent: u64, compounded: u64) -> ProgramResult {
+        self.liquidity_amount = self
+            .liquidity_amount
+            .checked_sub(principal_spent)
+            .and_then(|amount| amount.checked_add(compounded))
+            .ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Principal, in liquidity tokens, spent on top of the yield on the next run
+    pub fn principal_per_run(&self) -> u64 {
+        match self.principal_mode {
+            PrincipalMode::YieldOnly => 0,
+            PrincipalMode::Fixed(amount) => amount.min(self.liquidity_amount),
+            PrincipalMode::Bps(bps) => {
+                ((self.liquidity_amount as u128) * (bps as u128) / (BPS_DENOMINATOR as u128)) as u64
+            }
+        }
+    }
+}
+
+/// Current layout version of DepositState. Bump it when fields are carved out of `reserved`,
+/// they read as zero on deposits written with an older version.
+pub const DEPOSIT_STATE_VERSION: u8 = 1;
+
+/// Max number of recipients a deposit's purchases can be split across. Sizes the `splits`
+/// array, so changing it changes the DepositState layout.
+pub const MAX_RECIPIENTS: usize = 4;
+
+#[derive(Clone, Copy, Default, AnchorSerialize, AnchorDeserialize)]
+pub struct RecipientSplit {
+    // Token account of dca_mint, or a system wallet for native SOL
+    pub recipient: Pubkey,
+    // Share of each purchase in basis points, weights of a deposit sum to BPS_DENOMINATOR
+    pub weight_bps: u16,
+}
+
+impl RecipientSplit {
+    pub const LEN: usize = 32 + 2;
+}
+
+/// Max number of target mints in a deposit's basket. Sizes the `basket` array, so changing it
+/// changes the DepositState layout.
+pub const MAX_BASKET_LEGS: usize = 4;
+
+/// Number of `remaining_accounts` describing one basket leg in run_basket_strategy
+pub const BASKET_LEG_ACCOUNTS: usize = 10;
+
+#[derive(Clone, Copy, Default, AnchorSerialize, AnchorDeserialize)]
+pub struct BasketLeg {
+    // Token mint bought by this leg
+    pub mint: Pubkey,
+    // Token account of mint receiving the leg's purchases
+    pub recipient: Pubkey,
+    // Share of the yield in basis points, weights of a basket sum to BPS_DENOMINATOR
+    pub weight_bps: u16,
+    // Open orders account of the leg's market, set on its first purchase
+    pub ooa: Option<Pubkey>,
+    // Cumulative amount of mint received by this leg
+    pub total_received: u64,
+}
+
+impl BasketLeg {
+    pub const LEN: usize = 32 + 32 + 2 + 33 + 8;
+}
+
+// Basket leg as supplied to set_basket, the recipient comes from remaining_accounts
+#[derive(Clone, AnchorSerialize, AnchorDeserialize)]
+pub struct BasketLegParams {
+    pub mint: Pubkey,
+    pub weight_bps: u16,
+}
+
+// Per leg order parameters of run_basket_strategy
+#[derive(Clone, AnchorSerialize, AnchorDeserialize)]
+pub struct BasketOrder {
+    pub side: Side,
+    pub min_expected_swap_amount: u64,
+}
+
+#[derive(Clone, Copy, Default, AnchorSerialize, AnchorDeserialize)]
+pub struct DepositBumps {
+    // DepositState PDA
+    pub deposit: u8,
+    // ExecutionHistory PDA
+    pub history: u8,
+    // Liquidity token account PDA
+    pub liquidity: u8,
+    // Collateral token account PDA
+    pub collateral: u8,
+    // Position NFT mint PDA
+    pub position: u8,
+}
+
+impl DepositBumps {
+    pub const LEN: usize = 5;
+}
+
+// DepositState as written before deposits were PDAs owned through a position NFT. Only read
+// by close_legacy_deposit
+#[derive(Clone, Default, AnchorSerialize, AnchorDeserialize)]
+pub struct LegacyDepositState {
+    pub user_authority: Pubkey,
+    pub collateral_account_key: Pubkey,
+    pub liquidity_amount: u64,
+    pub collateral_amount: u64,
+    pub schedule: DcaSchedule,
+    pub reserve_account: Pubkey,
+    pub dca_mint: Pubkey,
+    pub dca_recipient: Pubkey,
+    pub ooa: Option<Pubkey>,
+    pub created_at: i64,
+    pub counter: u16,
+    pub nonce: u8,
+}
+
+impl LegacyDepositState {
+    // Legacy accounts were sized off the default state, so ooa never fit more than None
+    pub const LEN: usize = 32 // user_authority
+        + 32 // collateral_account_key
+        + 8 // liquidity_amount
+        + 8 // collateral_amount
+        + 1 // schedule
+        + 32 // reserve_account
+        + 32 // dca_mint
+        + 32 // dca_recipient
+        + 1 // ooa
+        + 8 // created_at
+        + 2 // counter
+        + 1; // nonce
+
+    /// Decodes a legacy deposit account, discriminator included. Legacy accounts share
+    /// DepositState's discriminator and are told apart by their size
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() != 8 + Self::LEN || data[..8] != DepositState::discriminator() {
+            return Err(ErrorCode::InvalidDepositVersion.into());
+        }
+        Self::try_from_slice(&data[8..]).map_err(|_| ErrorCode::InvalidDepositVersion.into())
+    }
+}
+
+/// Number of executions retained by an ExecutionHistory account
+pub const EXECUTION_HISTORY_LEN: usize = 16;
+
+/// Number of `remaining_accounts` describing one deposit in run_dca_batch
+pub const BATCH_GROUP_LEN: usize = 6;
+
+#[account]
+#[derive(Default)]
+pub struct ExecutionHistory {
+    // DepositState account this history belongs to
+    pub deposit: Pubkey,
+    // Index of the slot the next record is written to
+    pub head: u8,
+    // Number of populated slots, saturates at EXECUTION_HISTORY_LEN
+    pub len: u8,
+    // Bump seed of the history PDA
+    pub bump: u8,
+    // Ring buffer of the most recent executions, oldest gets overwritten first
+    pub records: [ExecutionRecord; EXECUTION_HISTORY_LEN],
+}
+
+impl ExecutionHistory {
+    pub const LEN: usize = 32 + 1 + 1 + 1 + EXECUTION_HISTORY_LEN * ExecutionRecord::LEN;
+
+    /// Appends a record, overwriting the oldest one once the buffer is full
+    pub fn push(&mut self, record: ExecutionRecord) {
+        self.records[self.head as usize] = record;
+        self.head = ((self.head as usize + 1) % EXECUTION_HISTORY_LEN) as u8;
+        if (self.len as usize) < EXECUTION_HISTORY_LEN {
+            self.len += 1;
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, AnchorSerialize, AnchorDeserialize)]
+pub struct ExecutionRecord {
+    // Unix timestamp of the execution
+    pub timestamp: i64,
+    // Liquidity tokens paid into the purchase, after the protocol fee. Includes any principal
+    // sold alongside the yield, so it's the cost basis of amount_received.
+    pub amount_spent: u64,
+    // Amount of the DCA token received
+    pub amount_received: u64,
+    // Liquidity tokens paid per DCA token, scaled by PRICE_PRECISION
+    pub average_price: u64,
+}
+
+impl ExecutionRecord {
+    pub const LEN: usize = 8 + 8 + 8 + 8;
+}
+
+#[account]
+#[derive(Default)]
+pub struct ReverseDcaState {
+    // Pubkey of the owner
+    pub user_authority: Pubkey,
+    // Token mint of the asset being sold
+    pub asset_mint: Pubkey,
+    // State owned token account holding the asset left to sell
+    pub asset_account: Pubkey,
+    // Fraction of the remaining asset sold per run, in basis points
+    pub sell_bps: u16,
+    // Selling schedule
+    pub schedule: DcaSchedule,
+    // Pubkey of reserve account of pool the proceeds are deposited into
+    pub reserve_account: Pubkey,
+    // Token mint of the reserve liquidity
+    pub liquidity_mint: Pubkey,
+    // State owned token account the sale proceeds settle into
+    pub liquidity_account: Pubkey,
+    // State owned token account holding the reserve collateral token
+    pub collateral_account: Pubkey,
+    // Proceeds deposited into the reserve so far, in liquidity tokens
+    pub liquidity_amount: u64,
+    // Reserve collateral tokens held
+    pub collateral_amount: u64,
+    // Cumulative amount of the asset sold
+    pub total_sold: u64,
+    // OOA Pubkey
+    pub ooa: Option<Pubkey>,
+    // Unix timestamp of creation
+    pub created_at: i64,
+    // Integer representing the amount of times a sale has executed
+    pub counter: u16,
+    // Unix timestamp of the last sale, 0 before the first one
+    pub last_run_at: i64,
+    // User chosen index, part of the ReverseDcaState PDA seeds
+    pub index: u64,
+    // Bump seeds of the PDAs created alongside the state
+    pub bumps: ReverseDcaBumps,
+}
+
+impl ReverseDcaState {
+    pub const LEN: usize = 32 // user_authority
+        + 32 // asset_mint
+        + 32 // asset_account
+        + 2 // sell_bps
+        + 1 // schedule
+        + 32 // reserve_account
+        + 32 // liquidity_mint
+        + 32 // liquidity_account
+        + 32 // collateral_account
+        + 8 // liquidity_amount
+        + 8 // collateral_amount
+        + 8 // total_sold
+        + 33 // ooa
+        + 8 // created_at
+        + 2 // counter
+        + 8 // last_run_at
+        + 8 // index
+        + ReverseDcaBumps::LEN; // bumps
+
+    // Signer seeds of the state PDA, which owns its token accounts and open orders
+    fn authority_seeds(&self) -> Vec<Vec<u8>> {
+        vec![
+            b"reverse".to_vec(),
+            self.user_authority.to_bytes().to_vec(),
+            self.asset_mint.to_bytes().to_vec(),
+            self.index.to_le_bytes().to_vec(),
+            vec![self.bumps.state],
+        ]
+    }
+}
+
+#[derive(Clone, Copy, Default, AnchorSerialize, AnchorDeserialize)]
+pub struct ReverseDcaBumps {
+    // ReverseDcaState PDA
+    pub state: u8,
+    // Asset token account PDA
+    pub asset: u8,
+    // Liquidity token account PDA
+    pub liquidity: u8,
+    // Collateral token account PDA
+    pub collateral: u8,
+}
+
+impl ReverseDcaBumps {
+    pub const LEN: usize = 4;
+}
+
+/// Max number of reserves in the config whitelist. Sizes the `reserve_whitelist` array, so
+/// changing it changes the Config layout.
+pub const MAX_WHITELISTED_RESERVES: usize = 16;
+
+/// Shortest timelock delay the admin can set, in seconds
+pub const MIN_TIMELOCK_DELAY: i64 = 24 * 60 * 60;
+
+#[account]
+#[derive(Default)]
+pub struct Config {
+    // Key allowed to queue, execute and cancel admin actions. May be a program derived
+    // address of a governance or multisig program
+    pub admin: Pubkey,
+    // Protocol fee taken from harvested liquidity, in basis points
+    pub fee_bps: u16,
+    // Owner of the token accounts collecting the fee
+    pub fee_recipient: Pubkey,
+    // Seconds an admin action has to wait between being queued and executed
+    pub timelock_delay: i64,
+    // Reserves deposits can be made into, the first whitelist_len entries are used. Any
+    // reserve is accepted until the first one is added, after which the last entry can't be
+    // removed
+    pub reserve_whitelist: [Pubkey; MAX_WHITELISTED_RESERVES],
+    pub whitelist_len: u8,
+    // Pending admin action and the unix timestamp it becomes executable at
+    pub queued_action: AdminAction,
+    pub queued_eta: i64,
+    // Bump seed of the config PDA
+    pub bump: u8,
+}
+
+impl Config {
+    pub const LEN: usize = 32 // admin
+        + 2 // fee_bps
+        + 32 // fee_recipient
+        + 8 // timelock_delay
+        + 32 * MAX_WHITELISTED_RESERVES // reserve_whitelist
+        + 1 // whitelist_len
+        + AdminAction::LEN // queued_action
+        + 8 // queued_eta
+        + 1; // bump
+
+    pub fn is_whitelisted(&self, reserve: &Pubkey) -> bool {
+        self.whitelist_len == 0
+            || self.reserve_whitelist[..self.whitelist_len as usize].contains(reserve)
+    }
+}
+
+#[derive(Clone, Copy, AnchorSerialize, AnchorDeserialize)]
+pub enum AdminAction {
+    None,
+    SetAdmin(Pubkey),
+    SetFee { fee_bps: u16, fee_recipient: Pubkey },
+    SetTimelockDelay(i64),
+    AddReserve(Pubkey),
+    RemoveReserve(Pubkey),
+}
+
+impl AdminAction {
+    pub const LEN: usize = 1 + 2 + 32;
+}
+
+impl Default for AdminAction {
+    fn default() -> Self {
+        AdminAction::None
+    }
+}
+
+/// Max number of keepers in the registry. Sizes the `keepers` array, so changing it changes
+/// the KeeperRegistry layout.
+pub const MAX_KEEPERS: usize = 8;
+
+#[account]
+#[derive(Default)]
+pub struct KeeperRegistry {
+    // Authorized keepers, the first keeper_count entries are used
+    pub keepers: [Pubkey; MAX_KEEPERS],
+    pub keeper_count: u8,
+    // Bump seed of the registry PDA
+    pub bump: u8,
+}
+
+impl KeeperRegistry {
+    pub const LEN: usize = 32 * MAX_KEEPERS + 1 + 1;
+
+    fn position(&self, keeper: &Pubkey) -> Option<usize> {
+        self.keepers[..self.keeper_count as usize]
+            .iter()
+            .position(|k| k == keeper)
+    }
+
+    pub fn contains(&self, keeper: &Pubkey) -> bool {
+        self.position(keeper).is_some()
+    }
+}
+
+#[account]
+#[derive(Default)]
+pub struct KeeperStats {
+    // Keeper key
+    pub keeper: Pubkey,
+    // Successful runs
+    pub runs: u64,
+    // Runs that failed off chain, as reported by the keeper
+    pub failures: u64,
+    // Unix timestamp of the last successful run
+    pub last_run: i64,
+    // Bump seed of the stats PDA
+    pub bump: u8,
+}
+
+impl KeeperStats {
+    pub const LEN: usize = 32 + 8 + 8 + 8 + 1;
+
+    // Counts a successful run at unix timestamp `now`
+    fn record_run(&mut self, now: i64) {
+        self.runs += 1;
+        self.last_run = now;
+    }
+}
+
+/// Fixed point scale of Vault::dca_per_share
+pub const SHARE_PRECISION: u128 = 1_000_000_000_000;
+
+#[account]
+#[derive(Default)]
+pub struct Vault {
+    // Reserve account the pooled liquidity is deposited into
+    pub reserve_account: Pubkey,
+    // Token mint of the reserve liquidity
+    pub liquidity_mint: Pubkey,
+    // Token mint the vault DCAs into
+    pub dca_mint: Pubkey,
+    // DCA schedule shared by every holder
+    pub schedule: DcaSchedule,
+    // Mint of the vault share tokens, the vault is its mint and freeze authority
+    pub share_mint: Pubkey,
+    // Vault owned token account liquidity passes through
+    pub liquidity_account: Pubkey,
+    // Vault owned token account holding the reserve collateral token
+    pub collateral_account: Pubkey,
+    // Vault owned token account holding purchased DCA tokens until they are claimed
+    pub dca_account: Pubkey,
+    // Outstanding vault shares
+    pub total_shares: u64,
+    // Pooled principal in liquidity tokens
+    pub liquidity_amount: u64,
+    // Reserve collateral tokens held by the vault
+    pub collateral_amount: u64,
+    // DCA tokens bought per share over the vault's lifetime, scaled by SHARE_PRECISION
+    pub dca_per_share: u128,
+    // OOA Pubkey
+    pub ooa: Option<Pubkey>,
+    // Integer representing the amount of times the vault's DCA has executed
+    pub counter: u16,
+    // Unix timestamp of the last run, 0 before the first one
+    pub last_run_at: i64,
+    // Bump seeds of the vault PDAs
+    pub bumps: VaultBumps,
+}
+
+impl Vault {
+    pub const LEN: usize = 32 // reserve_account
+        + 32 // liquidity_mint
+        + 32 // dca_mint
+        + 1 // schedule
+        + 32 // share_mint
+        + 32 // liquidity_account
+        + 32 // collateral_account
+        + 32 // dca_account
+        + 8 // total_shares
+        + 8 // liquidity_amount
+        + 8 // collateral_amount
+        + 16 // dca_per_share
+        + 33 // ooa
+        + 2 // counter
+        + 8 // last_run_at
+        + VaultBumps::LEN; // bumps
+
+    // Signer seeds of the vault PDA, which owns the vault's token accounts and share mint
+    fn authority_seeds(&self) -> Vec<Vec<u8>> {
+        vec![
+            b"vault".to_vec(),
+            self.reserve_account.to_bytes().to_vec(),
+            self.dca_mint.to_bytes().to_vec(),
+            vec![self.schedule as u8],
+            vec![self.bumps.vault],
+        ]
+    }
+}
+
+#[derive(Clone, Copy, Default, AnchorSerialize, AnchorDeserialize)]
+pub struct VaultBumps {
+    // Vault PDA
+    pub vault: u8,
+    // Liquidity token account PDA
+    pub liquidity: u8,
+    // Collateral token account PDA
+    pub collateral: u8,
+    // DCA token account PDA
+    pub dca: u8,
+    // Share mint PDA
+    pub share_mint: u8,
+}
+
+impl VaultBumps {
+    pub const LEN: usize = 5;
+}
+
+#[account]
+#[derive(Default)]
+pub struct VaultPosition {
+    // Vault the position belongs to
+    pub vault: Pubkey,
+    // Depositor the position belongs to
+    pub owner: Pubkey,
+    // Owner's token account of the share mint. It stays frozen, so shares only move through
+    // vault instructions that settle the accrual first
+    pub share_account: Pubkey,
+    // Vault shares held in share_account
+    pub shares: u64,
+    // shares * Vault::dca_per_share at the last checkpoint, scaled down by SHARE_PRECISION
+    pub reward_debt: u128,
+    // DCA tokens accrued but not claimed yet
+    pub pending: u64,
+    // Bump seed of the position PDA
+    pub bump: u8,
+}
+
+impl VaultPosition {
+    pub const LEN: usize = 32 + 32 + 32 + 8 + 16 + 8 + 1;
+
+    // Moves the DCA tokens accrued since the last checkpoint into `pending`
+    fn accrue(&mut self, dca_per_share: u128) -> ProgramResult {
+        let accrued = (self.shares as u128) * dca_per_share / SHARE_PRECISION - self.reward_debt;
+        self.pending = self
+            .pending
+            .checked_add(accrued as u64)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.checkpoint(dca_per_share);
+        Ok(())
+    }
 
-    pub liquidity_recipient: Account<'info, TokenAccount>,
+    // Resets the reward debt, called whenever the position's shares change
+    fn checkpoint(&mut self, dca_per_share: u128) {
+        self.reward_debt = (self.shares as u128) * dca_per_share / SHARE_PRECISION;
+    }
 
-    // Solend, Jet, or Port program
-    pub lending_program: AccountInfo<'info>,
+    // Moves `shares` to `recipient`, settling both positions' accrual at `dca_per_share` first
+    fn transfer_shares(
+        &mut self,
+        recipient: &mut VaultPosition,
+        shares: u64,
+        dca_per_share: u128,
+    ) -> ProgramResult {
+        self.accrue(dca_per_share)?;
+        recipient.accrue(dca_per_share)?;
+        self.shares = self
+            .shares
+            .checked_sub(shares)
+            .ok_or(ErrorCode::MathOverflow)?;
+        recipient.shares = recipient
+            .shares
+            .checked_add(shares)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.checkpoint(dca_per_share);
+        recipient.checkpoint(dca_per_share);
+        Ok(())
+    }
+}
 
-    // RedeeemReserveCollateral accounts
-    // Source token account for reserve collateral token
-    #[account(constraint = source_collateral.to_account_info().key == transfer_authority.key)]
-    pub source_collateral: Account<'info, TokenAccount>,
-    #[account(
-        mut,
-        constraint = *serum_recipient.to_account_info().key == deposit_state.dca_recipient
-    )]
-    pub serum_recipient: Account<'info, TokenAccount>,
-    // Refreshed reserve account
-    // #[account(constraint = refreshed_reserve.key == reserve.key)]
-    pub refreshed_reserve: AccountInfo<'info>,
-    // Reserve collateral mint account
-    pub reserve_collateral_mint: AccountInfo<'info>,
-    // Reserve liquidity supply SPL Token account.
-    pub reserve_liquidity: AccountInfo<'info>,
+// Lending accounts are the accounts used to move liquidity in and out of a
+// reserve minus the token accounts, authority and common accounts.
+#[derive(Accounts, Clone)]
+pub struct LendingAccounts<'info> {
+    // Solend program, every CPI into it is signed by a PDA
+    #[account(address = solend_program::ID)]
+    lending_program: AccountInfo<'info>,
+    // Reserve state account
+    #[account(mut)]
+    reserve: AccountInfo<'info>,
+    // Token mint for reserve collateral token
+    #[account(mut)]
+    reserve_collateral_mint: AccountInfo<'info>,
+    // Reserve liquidity supply SPL token account
+    #[account(mut)]
+    reserve_liquidity_supply: AccountInfo<'info>,
     // Lending market account
-    pub lending_market: AccountInfo<'info>,
-    // Lending market authority - PDA
-    pub lending_market_authority: AccountInfo<'info>,
-    // User transfer authority
-    #[account(seeds = [&user_authority.key.to_bytes()[..32], &refreshed_reserve.key.to_bytes()[..32], &[nonce]], bump = _bump)]
-    pub transfer_authority: AccountInfo<'info>,
-
-    pub clock: AccountInfo<'info>,
-    pub rent: AccountInfo<'info>,
-    pub token_program_id: AccountInfo<'info>,
+    lending_market: AccountInfo<'info>,
+    // Lending market authority (PDA)
+    lending_market_authority: AccountInfo<'info>,
 }
 
-#[account]
-#[derive(Default)]
-pub struct DepositState {
-    // Pubkey of depositor that called ix
-    pub user_authority: Pubkey,
-    // Pubkey of account holding the reserve collateral token
-    // Used for AddToDeposit context struct constraints
-    pub collateral_account_key: Pubkey,
-    // Current amount of liquidity tokens deposited
-    // Update on withdraw or modification
-    pub liquidity_amount: u64,
-    // Current amount of reserve collateral tokens being controlled by PDA
-    // Update on withdraw or modification
-    pub collateral_amount: u64,
-    // DCA schedule for deposit
-    pub schedule: DcaSchedule,
-    // Pubkey of reserve account of pool where liquidity is deposited
-    pub reserve_account: Pubkey,
-    // Token mint of token to run dca strategy on
-    pub dca_mint: Pubkey,
-    // Set this as ATA of signer
-    pub dca_recipient: Pubkey,
-    // OOA Pubkey
-    pub ooa: Option<Pubkey>,
+impl<'info> LendingAccounts<'info> {
+    // Deposits `amount` of liquidity from `source_liquidity` into the reserve, minting
+    // collateral into `destination_collateral`. `authority` owns both accounts.
+    #[allow(clippy::too_many_arguments)]
+    fn deposit(
+        &self,
+        source_liquidity: &AccountInfo<'info>,
+        destination_collateral: &AccountInfo<'info>,
+        authority: &AccountInfo<'info>,
+        clock: &AccountInfo<'info>,
+        token_program: &AccountInfo<'info>,
+        signer: &[&[&[u8]]],
+        amount: u64,
+    ) -> ProgramResult {
+        let cpi_accounts = DepositReserveLiquidity {
+            lending_program: self.lending_program.clone(),
+            source_liquidity: source_liquidity.clone(),
+            destination_collateral_account: destination_collateral.clone(),
+            reserve_account: self.reserve.clone(),
+            reserve_collateral_mint: self.reserve_collateral_mint.clone(),
+            reserve_liquidity_supply: self.reserve_liquidity_supply.clone(),
+            lending_market_account: self.lending_market.clone(),
+            lending_market_authority: self.lending_market_authority.clone(),
+            transfer_authority: authority.clone(),
+            clock: clock.clone(),
+            token_program_id: token_program.clone(),
+        };
+        let cpi_ctx =
+            CpiContext::new_with_signer(self.lending_program.clone(), cpi_accounts, signer);
+        deposit_reserve_liquidity(cpi_ctx, amount)
+    }
 
-    // Unix timestamp of deposit
-    pub created_at: i64,
-    // Integer representing the amount of times a DCA has executed
-    pub counter: u16,
-    // Nonce
-    pub nonce: u8,
+    // Redeems `amount` of collateral from `source_collateral`, paying the liquidity into
+    // `destination_liquidity`. `authority` owns both accounts.
+    #[allow(clippy::too_many_arguments)]
+    fn redeem(
+        &self,
+        source_collateral: &AccountInfo<'info>,
+        destination_liquidity: &AccountInfo<'info>,
+        authority: &AccountInfo<'info>,
+        clock: &AccountInfo<'info>,
+        token_program: &AccountInfo<'info>,
+        signer: &[&[&[u8]]],
+        amount: u64,
+    ) -> ProgramResult {
+        let cpi_accounts = RedeemReserveCollateral {
+            lending_program: self.lending_program.clone(),
+            source_collateral: source_collateral.clone(),
+            destination_liquidity: destination_liquidity.clone(),
+            refreshed_reserve_account: self.reserve.clone(),
+            reserve_collateral_mint: self.reserve_collateral_mint.clone(),
+            reserve_liquidity: self.reserve_liquidity_supply.clone(),
+            lending_market: self.lending_market.clone(),
+            lending_market_authority: self.lending_market_authority.clone(),
+            user_transfer_authority: authority.clone(),
+            clock: clock.clone(),
+            token_program_id: token_program.clone(),
+        };
+        let cpi_ctx =
+            CpiContext::new_with_signer(self.lending_program.clone(), cpi_accounts, signer);
+        redeem_reserve_collateral(cpi_ctx, amount)
+    }
 }
 
 // Market accounts are the accounts used to place orders against the dex minus
@@ -619,6 +4770,8 @@ pub struct MarketAccounts<'info> {
 struct OrderbookClient<'info> {
     market: MarketAccounts<'info>,
     authority: AccountInfo<'info>,
+    // Signer seeds of `authority`, a PDA of this program
+    authority_seeds: Vec<Vec<u8>>,
     // pc_wallet: AccountInfo<'info>,
     dex_program: AccountInfo<'info>,
     token_program: AccountInfo<'info>,
@@ -703,16 +4856,18 @@ impl<'info> OrderbookClient<'info> {
             token_program: self.token_program.clone(),
             rent: self.rent.clone(),
         };
-        let mut ctx = CpiContext::new(self.dex_program.clone(), dex_accs);
+        let seeds = seed_slices(&self.authority_seeds);
+        let signer = &[&seeds[..]];
+        let mut ctx = CpiContext::new_with_signer(self.dex_program.clone(), dex_accs, signer);
         if let Some(referral) = referral {
             ctx = ctx.with_remaining_accounts(vec![referral]);
         }
         dex::new_order_v3(
             ctx,
             side.into(),
-            NonZeroU64::new(limit_price).unwrap(),
-            NonZeroU64::new(max_coin_qty).unwrap(),
-            NonZeroU64::new(max_native_pc_qty).unwrap(),
+            NonZeroU64::new(limit_price).ok_or(ErrorCode::OrderTooSmall)?,
+            NonZeroU64::new(max_coin_qty).ok_or(ErrorCode::OrderTooSmall)?,
+            NonZeroU64::new(max_native_pc_qty).ok_or(ErrorCode::OrderTooSmall)?,
             SelfTradeBehavior::DecrementTake,
             OrderType::ImmediateOrCancel,
             client_order_id,
@@ -720,10 +4875,13 @@ impl<'info> OrderbookClient<'info> {
         )
     }
 
+    // Settles the open orders account, paying base currency into `coin_wallet` and quote
+    // currency into `pc_wallet`.
     fn settle(
         &self,
         referral: Option<AccountInfo<'info>>,
-        quote_wallet: &Account<'info, TokenAccount>,
+        coin_wallet: &AccountInfo<'info>,
+        pc_wallet: &AccountInfo<'info>,
     ) -> ProgramResult {
         let settle_accs = dex::SettleFunds {
             market: self.market.market.clone(),
@@ -731,12 +4889,14 @@ impl<'info> OrderbookClient<'info> {
             open_orders_authority: self.authority.clone(),
             coin_vault: self.market.coin_vault.clone(),
             pc_vault: self.market.pc_vault.clone(),
-            coin_wallet: self.market.destination_liquidity.to_account_info().clone(),
-            pc_wallet: quote_wallet.to_account_info().clone(),
+            coin_wallet: coin_wallet.clone(),
+            pc_wallet: pc_wallet.clone(),
             vault_signer: self.market.vault_signer.clone(),
             token_program: self.token_program.clone(),
         };
-        let mut ctx = CpiContext::new(self.dex_program.clone(), settle_accs);
+        let seeds = seed_slices(&self.authority_seeds);
+        let signer = &[&seeds[..]];
+        let mut ctx = CpiContext::new_with_signer(self.dex_program.clone(), settle_accs, signer);
         if let Some(referral) = referral {
             ctx = ctx.with_remaining_accounts(vec![referral]);
         }
@@ -749,15 +4909,14 @@ impl<'info> OrderbookClient<'info> {
 /// Derive the pubkey of the PDA meant to be in control of the source liquidity token account
 /// and reserve collateral destination account
 // fn derive_deposit_authority(
-//     user: &AccountInfo,
-//     reserve: &AccountInfo,
+//     deposit: &AccountInfo,
 //     program_id: &Pubkey,
 //     nonce: u8,
 // ) -> Result<Pubkey> {
 //     Pubkey::create_program_address(
 //         &[
-//             &user.key.to_bytes()[..32],    // Signer
-//             &reserve.key.to_bytes()[..32], // Solend reserve account
+//             b"authority",                  // Static prefix
+//             &deposit.key.to_bytes()[..32], // DepositState account
 //             &[nonce],                      // Nonce - usually 0
 //         ],
 //         program_id,
@@ -765,14 +4924,508 @@ impl<'info> OrderbookClient<'info> {
 //     .or(Err(ErrorCode::InvalidDerivedAuthority.into()))
 // }
 
-fn validate_admin(ctx: &Context<RunDcaStrategy>) -> ProgramResult {
-    if *ctx.accounts.user_authority.key != fee_recipient::ID {
-        return Err(ErrorCode::InvalidAdmin.into());
+// Moves `amount` of liquidity from the user into a deposit's PDA owned liquidity account.
+// Native SOL is wrapped straight from the user's wallet, any other mint is transferred out
+// of the user's `user_liquidity` token account.
+fn fund_liquidity<'info>(
+    user_authority: &AccountInfo<'info>,
+    user_liquidity: &AccountInfo<'info>,
+    source_liquidity: &AccountInfo<'info>,
+    liquidity_mint: &Pubkey,
+    system_program: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    amount: u64,
+) -> ProgramResult {
+    if *liquidity_mint == native_mint::ID {
+        invoke(
+            &system_instruction::transfer(user_authority.key, source_liquidity.key, amount),
+            &[
+                user_authority.clone(),
+                source_liquidity.clone(),
+                system_program.clone(),
+            ],
+        )?;
+        return invoke(
+            &spl_token::instruction::sync_native(&token::ID, source_liquidity.key)?,
+            &[source_liquidity.clone(), token_program.clone()],
+        );
+    }
+
+    if token::accessor::authority(user_liquidity)? != *user_authority.key
+        || token::accessor::mint(user_liquidity)? != *liquidity_mint
+    {
+        return Err(ErrorCode::InvalidLiquidityAccount.into());
+    }
+    let transfer_accounts = token::Transfer {
+        from: user_liquidity.clone(),
+        to: source_liquidity.clone(),
+        authority: user_authority.clone(),
+    };
+    let transfer_ctx = CpiContext::new(token_program.clone(), transfer_accounts);
+    token::transfer(transfer_ctx, amount)
+}
+
+// Creates and initializes the temporary PDA owned token account purchases settle into when
+// they are not paid straight to dca_recipient, i.e. wSOL for native SOL purchases or split
+// proceeds. `seeds` are the signer seeds of `payout_account`.
+#[allow(clippy::too_many_arguments)]
+fn create_payout_account<'info>(
+    payer: &AccountInfo<'info>,
+    payout_account: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    rent: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    let space = spl_token::state::Account::LEN;
+    let lamports = Rent::from_account_info(rent)?.minimum_balance(space);
+    invoke_signed(
+        &system_instruction::create_account(
+            payer.key,
+            payout_account.key,
+            lamports,
+            space as u64,
+            &token::ID,
+        ),
+        &[
+            payer.clone(),
+            payout_account.clone(),
+            system_program.clone(),
+        ],
+        &[seeds],
+    )?;
+
+    let init_accounts = token::InitializeAccount {
+        account: payout_account.clone(),
+        mint: mint.clone(),
+        authority: authority.clone(),
+        rent: rent.clone(),
+    };
+    token::initialize_account(CpiContext::new(token_program.clone(), init_accounts))
+}
+
+// Creates a vault's share mint at its PDA with the vault as mint and freeze authority.
+// `seeds` are the signer seeds of `share_mint`.
+#[allow(clippy::too_many_arguments)]
+fn create_share_mint<'info>(
+    payer: &AccountInfo<'info>,
+    share_mint: &AccountInfo<'info>,
+    vault: &AccountInfo<'info>,
+    decimals: u8,
+    rent: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    let space = spl_token::state::Mint::LEN;
+    let lamports = Rent::from_account_info(rent)?.minimum_balance(space);
+    invoke_signed(
+        &system_instruction::create_account(
+            payer.key,
+            share_mint.key,
+            lamports,
+            space as u64,
+            &token::ID,
+        ),
+        &[payer.clone(), share_mint.clone(), system_program.clone()],
+        &[seeds],
+    )?;
+    invoke(
+        &spl_token::instruction::initialize_mint(
+            &token::ID,
+            share_mint.key,
+            vault.key,
+            Some(vault.key),
+            decimals,
+        )?,
+        &[share_mint.clone(), rent.clone(), token_program.clone()],
+    )
+}
+
+// Freezes or thaws a position's share token account. Share accounts are only thawed for the
+// length of a vault instruction, so shares can't move without their accrual being settled.
+fn set_shares_frozen<'info>(
+    frozen: bool,
+    share_account: &AccountInfo<'info>,
+    share_mint: &AccountInfo<'info>,
+    vault: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    signer: &[&[&[u8]]],
+) -> ProgramResult {
+    let instruction = if frozen {
+        spl_token::instruction::freeze_account(
+            &token::ID,
+            share_account.key,
+            share_mint.key,
+            vault.key,
+            &[],
+        )?
+    } else {
+        spl_token::instruction::thaw_account(
+            &token::ID,
+            share_account.key,
+            share_mint.key,
+            vault.key,
+            &[],
+        )?
+    };
+    invoke_signed(
+        &instruction,
+        &[
+            share_account.clone(),
+            share_mint.clone(),
+            vault.clone(),
+            token_program.clone(),
+        ],
+        signer,
+    )
+}
+
+// Moves `amount` lamports out of the PDA `from`, which holds no data. `signer` are its seeds.
+fn transfer_lamports<'info>(
+    from: &AccountInfo<'info>,
+    to: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    signer: &[&[&[u8]]],
+    amount: u64,
+) -> ProgramResult {
+    invoke_signed(
+        &system_instruction::transfer(from.key, to.key, amount),
+        &[from.clone(), to.clone(), system_program.clone()],
+        signer,
+    )
+}
+
+// Checks `dca_recipient` is `wallet`'s associated token account of `dca_mint`, creating it
+// at the expense of `payer` if it doesn't exist yet. For native SOL the recipient must be
+// the wallet itself.
+#[allow(clippy::too_many_arguments)]
+fn init_dca_recipient<'info>(
+    payer: &AccountInfo<'info>,
+    wallet: &AccountInfo<'info>,
+    dca_recipient: &AccountInfo<'info>,
+    dca_mint: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    rent: &AccountInfo<'info>,
+    associated_token_program: &AccountInfo<'info>,
+) -> ProgramResult {
+    if *dca_mint.key == native_mint::ID {
+        if dca_recipient.key != wallet.key {
+            return Err(ErrorCode::InvalidRecipient.into());
+        }
+        return Ok(());
+    }
+
+    init_associated_account(
+        payer,
+        wallet,
+        dca_recipient,
+        dca_mint,
+        system_program,
+        token_program,
+        rent,
+        associated_token_program,
+    )
+}
+
+// Checks `account` is `wallet`'s associated token account of `mint`, creating it at the
+// expense of `payer` if it doesn't exist yet.
+#[allow(clippy::too_many_arguments)]
+fn init_associated_account<'info>(
+    payer: &AccountInfo<'info>,
+    wallet: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    rent: &AccountInfo<'info>,
+    associated_token_program: &AccountInfo<'info>,
+) -> ProgramResult {
+    if *account.key != get_associated_token_address(wallet.key, mint.key) {
+        return Err(ErrorCode::InvalidAssociatedAccount.into());
+    }
+    if account.data_is_empty() {
+        invoke(
+            &create_associated_token_account(payer.key, wallet.key, mint.key),
+            &[
+                payer.clone(),
+                account.clone(),
+                wallet.clone(),
+                mint.clone(),
+                system_program.clone(),
+                token_program.clone(),
+                rent.clone(),
+                associated_token_program.clone(),
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+// Checks a split `recipient` can receive `dca_mint`: a token account of that mint, or a
+// system wallet for native SOL.
+fn validate_split_recipient(recipient: &AccountInfo, dca_mint: &Pubkey) -> ProgramResult {
+    let valid = if *dca_mint == native_mint::ID {
+        *recipient.owner == system_program::ID
+    } else {
+        *recipient.owner == token::ID && token::accessor::mint(recipient)? == *dca_mint
+    };
+    if !valid {
+        return Err(ErrorCode::InvalidRecipient.into());
+    }
+    Ok(())
+}
+
+// Checks the supplied mints belong to the given lending reserve
+fn validate_reserve_mints(
+    reserve: &AccountInfo,
+    liquidity_mint: &Pubkey,
+    collateral_mint: &Pubkey,
+) -> ProgramResult {
+    let reserve: Reserve = Reserve::unpack(&reserve.data.borrow())?;
+    if reserve.liquidity.mint_pubkey != *liquidity_mint
+        || reserve.collateral.mint_pubkey != *collateral_mint
+    {
+        return Err(ErrorCode::InvalidReserveMint.into());
+    }
+    Ok(())
+}
+
+// Accepts the deposit's delegate, or the holder of its position NFT.
+fn validate_manager(accounts: &ManageDeposit) -> ProgramResult {
+    let deposit_state = &accounts.deposit_state;
+    if deposit_state.delegate == Some(*accounts.user_authority.key) {
+        return Ok(());
+    }
+
+    let position_token_account = &accounts.position_token_account;
+    if *position_token_account.owner != token::ID {
+        return Err(ErrorCode::InvalidManager.into());
+    }
+    let position = spl_token::state::Account::unpack(&position_token_account.data.borrow())?;
+    if !holds_position(&position, deposit_state, accounts.user_authority.key) {
+        return Err(ErrorCode::InvalidManager.into());
+    }
+    Ok(())
+}
+
+// Whether `position` is `holder`'s token account holding the position NFT of `deposit_state`,
+// which is what proves ownership of a deposit.
+fn holds_position(
+    position: &spl_token::state::Account,
+    deposit_state: &DepositState,
+    holder: &Pubkey,
+) -> bool {
+    position.mint == deposit_state.position_mint
+        && position.owner == *holder
+        && position.amount == 1
+}
+
+// Accepts keepers listed in the keeper registry.
+fn validate_keeper(registry: &KeeperRegistry, keeper: &AccountInfo) -> ProgramResult {
+    if !registry.contains(keeper.key) {
+        return Err(ErrorCode::InvalidKeeper.into());
+    }
+    Ok(())
+}
+
+// Splits the yield accrued on `collateral_amount` above the `liquidity_amount` principal into
+// the part compounded into the principal, per `compound_bps`, and the part harvested. Returns
+// the compounded liquidity along with the amount of collateral to redeem to harvest the rest,
+// up to `spend_cap`, plus `principal_amount` of the principal. Yield over the cap stays in the
+// reserve unaccounted for, to be harvested on a later run.
+fn harvestable_yield(
+    reserve: &AccountInfo,
+    collateral_amount: u64,
+    liquidity_amount: u64,
+    principal_amount: u64,
+    compound_bps: u16,
+    spend_cap: u64,
+) -> Result<(u64, u64)> {
+    let reserve: Reserve = Reserve::unpack(&reserve.data.borrow())?;
+    let exchange_rate = reserve.collateral_exchange_rate()?;
+    let liquidity_value = exchange_rate.collateral_to_liquidity(collateral_amount)?;
+    let (compounded_amount, redeem_amount) = split_yield(
+        liquidity_value,
+        liquidity_amount,
+        principal_amount,
+        compound_bps,
+        spend_cap,
+    )?;
+    let collateral_to_redeem = exchange_rate
+        .liquidity_to_collateral(redeem_amount)?
+        .min(collateral_amount);
+    Ok((compounded_amount, collateral_to_redeem))
+}
+
+// Protocol fee on `amount` of harvested liquidity at `fee_bps`, rounded down.
+fn protocol_fee(amount: u64, fee_bps: u16) -> Result<u64> {
+    if fee_bps as u64 > BPS_DENOMINATOR {
+        return Err(ErrorCode::InvalidBasisPoints.into());
+    }
+    mul_div(amount, fee_bps as u64, BPS_DENOMINATOR)
+}
+
+// Fails if `delay` is too short for the timelock to give users time to react.
+fn check_timelock_delay(delay: i64) -> ProgramResult {
+    if delay < MIN_TIMELOCK_DELAY {
+        return Err(ErrorCode::InvalidTimelockDelay.into());
+    }
+    Ok(())
+}
+
+// Splits the yield of a position worth `liquidity_value` on `liquidity_amount` of principal.
+// `compound_bps` of it is left in the reserve and the rest, capped at `spend_cap`, is redeemed
+// along with `principal_amount`. Returns the compounded amount and the liquidity to redeem.
+fn split_yield(
+    liquidity_value: u64,
+    liquidity_amount: u64,
+    principal_amount: u64,
+    compound_bps: u16,
+    spend_cap: u64,
+) -> Result<(u64, u64)> {
+    let yield_amount = liquidity_value.saturating_sub(liquidity_amount);
+    let compounded_amount = mul_div(yield_amount, compound_bps as u64, BPS_DENOMINATOR)?;
+    let redeem_amount = yield_amount
+        .checked_sub(compounded_amount)
+        .ok_or(ErrorCode::MathOverflow)?
+        .min(spend_cap)
+        .checked_add(principal_amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    Ok((compounded_amount, redeem_amount))
+}
+
+// Moves the protocol fee on `amount` of harvested liquidity out of `from` into `fee_account`.
+// Returns the fee taken.
+fn charge_fee<'info>(
+    config: &Config,
+    amount: u64,
+    from: &AccountInfo<'info>,
+    fee_account: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    signer: &[&[&[u8]]],
+) -> Result<u64> {
+    let fee = protocol_fee(amount, config.fee_bps)?;
+    if fee > 0 {
+        let transfer_accounts = token::Transfer {
+            from: from.clone(),
+            to: fee_account.clone(),
+            authority: authority.clone(),
+        };
+        token::transfer(
+            CpiContext::new_with_signer(token_program.clone(), transfer_accounts, signer),
+            fee,
+        )?;
+    }
+    Ok(fee)
+}
+
+// Reads the Pyth price of a DCA token in the reserve liquidity token and converts it to native
+// liquidity units per native DCA unit scaled by PRICE_PRECISION, like average_price. `slot` is
+// the current slot, prices published more than MAX_ORACLE_STALENESS_SLOTS before are refused.
+fn oracle_price(
+    oracle: &AccountInfo,
+    liquidity_decimals: u8,
+    dca_decimals: u8,
+    slot: u64,
+) -> Result<u64> {
+    if *oracle.owner != pyth_program::ID {
+        return Err(ErrorCode::InvalidOracle.into());
+    }
+    let data = oracle.data.borrow();
+    if data.len() < std::mem::size_of::<pyth_client::Price>() {
+        return Err(ErrorCode::InvalidOracle.into());
+    }
+    let price_account = pyth_client::cast::<pyth_client::Price>(&data);
+    if price_account.magic != pyth_client::MAGIC
+        || price_account.atype != AccountType::Price as u32
+        || !matches!(price_account.agg.status, PriceStatus::Trading)
+    {
+        return Err(ErrorCode::InvalidOracle.into());
+    }
+    check_price(
+        price_account.agg.price,
+        price_account.agg.conf,
+        price_account.agg.pub_slot,
+        slot,
+    )?;
+    scale_price(
+        price_account.agg.price,
+        price_account.expo + liquidity_decimals as i32 - dca_decimals as i32,
+    )
+}
+
+// Fails unless an aggregate price published at `pub_slot` is positive, fresh at `slot`, and
+// has a confidence interval within MAX_ORACLE_CONFIDENCE_BPS of it.
+fn check_price(price: i64, conf: u64, pub_slot: u64, slot: u64) -> ProgramResult {
+    if price <= 0 {
+        return Err(ErrorCode::InvalidOracle.into());
+    }
+    if slot.saturating_sub(pub_slot) > MAX_ORACLE_STALENESS_SLOTS {
+        return Err(ErrorCode::StaleOracle.into());
+    }
+    if (conf as u128) * (BPS_DENOMINATOR as u128)
+        > (price as u128) * (MAX_ORACLE_CONFIDENCE_BPS as u128)
+    {
+        return Err(ErrorCode::OracleTooUncertain.into());
     }
     Ok(())
 }
 
-#[derive(Clone, AnchorDeserialize, AnchorSerialize)]
+// Converts a positive price with decimal `exponent` to a PRICE_PRECISION fixed point.
+fn scale_price(price: i64, exponent: i32) -> Result<u64> {
+    let price = (price as u128) * PRICE_PRECISION;
+    let scale = 10u128
+        .checked_pow(exponent.unsigned_abs())
+        .ok_or(ErrorCode::MathOverflow)?;
+    let price = if exponent >= 0 {
+        price.checked_mul(scale)
+    } else {
+        price.checked_div(scale)
+    }
+    .ok_or(ErrorCode::MathOverflow)?;
+    if price > u64::MAX as u128 {
+        return Err(ErrorCode::MathOverflow.into());
+    }
+    Ok(price as u64)
+}
+
+// Returns `amount * numerator / denominator` without intermediate overflow.
+fn mul_div(amount: u64, numerator: u64, denominator: u64) -> Result<u64> {
+    if denominator == 0 {
+        return Err(ErrorCode::MathOverflow.into());
+    }
+    let result = (amount as u128) * (numerator as u128) / (denominator as u128);
+    if result > u64::MAX as u128 {
+        return Err(ErrorCode::MathOverflow.into());
+    }
+    Ok(result as u64)
+}
+
+// Shares issued for `minted_collateral` deposited into a vault holding `collateral_before`
+// against `total_shares`. Pricing off collateral prices shares at the vault's full value, yield
+// not harvested yet included, so joining or leaving a vault never moves value between holders.
+fn vault_shares_for(
+    minted_collateral: u64,
+    collateral_before: u64,
+    total_shares: u64,
+) -> Result<u64> {
+    if total_shares == 0 || collateral_before == 0 {
+        return Ok(minted_collateral);
+    }
+    mul_div(minted_collateral, total_shares, collateral_before)
+}
+
+// Borrows owned signer seeds in the form CPI contexts expect.
+fn seed_slices(seeds: &[Vec<u8>]) -> Vec<&[u8]> {
+    seeds.iter().map(|seed| seed.as_slice()).collect()
+}
+
+#[derive(Clone, Copy, AnchorDeserialize, AnchorSerialize)]
 pub enum DcaSchedule {
     Daily,
     Weekly,
@@ -781,12 +5434,129 @@ pub enum DcaSchedule {
     Quarterly,
 }
 
+impl DcaSchedule {
+    /// Seconds between two runs. Months count as 30 days and quarters as 91
+    pub fn interval(&self) -> i64 {
+        const DAY: i64 = 24 * 60 * 60;
+        match self {
+            DcaSchedule::Daily => DAY,
+            DcaSchedule::Weekly => 7 * DAY,
+            DcaSchedule::Biweekly => 14 * DAY,
+            DcaSchedule::Monthly => 30 * DAY,
+            DcaSchedule::Quarterly => 91 * DAY,
+        }
+    }
+}
+
+#[derive(Clone, Copy, AnchorDeserialize, AnchorSerialize)]
+pub enum PrincipalMode {
+    // Only yield above the principal is spent
+    YieldOnly,
+    // Fixed amount of liquidity tokens spent per run, until the principal runs out
+    Fixed(u64),
+    // Basis points of the remaining principal spent per run
+    Bps(u16),
+}
+
+impl PrincipalMode {
+    pub const LEN: usize = 1 + 8;
+}
+
+#[derive(Clone, Copy, AnchorDeserialize, AnchorSerialize)]
+pub enum DcaStrategy {
+    // Spend all harvested yield every run
+    FixedSize,
+    // Spend what brings the position's value to `value_per_run` times the number of runs,
+    // banking the rest of the yield
+    ValueAveraging { value_per_run: u64, runs: u32 },
+}
+
+impl DcaStrategy {
+    pub const LEN: usize = 1 + 8 + 4;
+}
+
+impl Default for DcaStrategy {
+    fn default() -> Self {
+        DcaStrategy::FixedSize
+    }
+}
+
+impl Default for PrincipalMode {
+    fn default() -> Self {
+        PrincipalMode::YieldOnly
+    }
+}
+
 impl Default for DcaSchedule {
     fn default() -> Self {
         DcaSchedule::Weekly
     }
 }
 
+/// Fixed point scale used for prices stored on chain
+pub const PRICE_PRECISION: u128 = 1_000_000_000;
+
+/// Basis points in a whole
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Max age of an oracle price, in slots
+pub const MAX_ORACLE_STALENESS_SLOTS: u64 = 25;
+
+/// Max width of an oracle price's confidence interval, in basis points of the price
+pub const MAX_ORACLE_CONFIDENCE_BPS: u64 = 200;
+
+// Fails unless a `schedule` interval passed since `last_run_at`, 0 before the first run, and
+// records `now` as the last run. Returns the number of whole intervals since the previous run,
+// 1 on the first one.
+fn advance_schedule(last_run_at: &mut i64, schedule: DcaSchedule, now: i64) -> Result<u64> {
+    let intervals = if *last_run_at == 0 {
+        1
+    } else {
+        let elapsed = now
+            .checked_sub(*last_run_at)
+            .ok_or(ErrorCode::MathOverflow)?;
+        (elapsed / schedule.interval()).max(0) as u64
+    };
+    if intervals == 0 {
+        return Err(ErrorCode::ScheduleNotDue.into());
+    }
+    *last_run_at = now;
+    Ok(intervals)
+}
+
+// Splits `amount` in proportion to `weights`. The last share takes the rounding remainder so
+// the shares always add up to `amount`.
+fn pro_rata(amount: u64, weights: &[u64]) -> Result<Vec<u64>> {
+    let total_weight = weights
+        .iter()
+        .try_fold(0u64, |total, weight| total.checked_add(*weight))
+        .ok_or(ErrorCode::MathOverflow)?;
+    let mut amount_left = amount;
+    let mut shares = Vec::with_capacity(weights.len());
+    for (i, weight) in weights.iter().enumerate() {
+        let share = if i == weights.len() - 1 {
+            amount_left
+        } else {
+            mul_div(amount, *weight, total_weight)?
+        };
+        amount_left = amount_left
+            .checked_sub(share)
+            .ok_or(ErrorCode::MathOverflow)?;
+        shares.push(share);
+    }
+    Ok(shares)
+}
+
+// Returns the amount of `spent` tokens paid per `received` token, scaled by
+// PRICE_PRECISION. Zero if nothing was received.
+fn average_price(spent: u64, received: u64) -> u64 {
+    if received == 0 {
+        return 0;
+    }
+    let price = (spent as u128) * PRICE_PRECISION / (received as u128);
+    std::cmp::min(price, u64::MAX as u128) as u64
+}
+
 // Returns the amount of lots for the base currency of a trade with `size`.
 fn coin_lots(market: &MarketState, size: u64) -> u64 {
     size.checked_div(market.coin_lot_size).unwrap()
@@ -802,7 +5572,7 @@ fn apply_risk_checks(event: DidSwap) -> Result<()> {
     Ok(())
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize)]
+#[derive(Clone, Copy, AnchorSerialize, AnchorDeserialize)]
 pub enum Side {
     Bid,
     Ask,
@@ -831,6 +5601,72 @@ pub enum ErrorCode {
     InvalidAdmin,
     #[msg("Collateral account is already empty")]
     CollateralAccountIsEmpty,
+    #[msg("Arithmetic overflow")]
+    MathOverflow,
+    #[msg("Mint does not match the lending reserve")]
+    InvalidReserveMint,
+    #[msg("Liquidity account must be a token account of the reserve mint owned by the user")]
+    InvalidLiquidityAccount,
+    #[msg("Recipient account does not match the deposit")]
+    InvalidRecipient,
+    #[msg("Open orders account does not match the deposit")]
+    InvalidOpenOrders,
+    #[msg("Account is not the associated token account of the wallet and mint")]
+    InvalidAssociatedAccount,
+    #[msg("Vault has no outstanding shares")]
+    VaultEmpty,
+    #[msg("Not enough vault shares")]
+    InsufficientShares,
+    #[msg("Batch accounts are malformed or don't belong together")]
+    InvalidBatch,
+    #[msg("Recipient weights must sum to 10000 basis points")]
+    InvalidSplitWeights,
+    #[msg("Basket is malformed or doesn't match the instruction")]
+    InvalidBasket,
+    #[msg("Fraction must be within 10000 basis points")]
+    InvalidBasisPoints,
+    #[msg("Price oracle is invalid or not trading")]
+    InvalidOracle,
+    #[msg("Instruction doesn't support the deposit's strategy")]
+    InvalidStrategy,
+    #[msg("Oracle price is above the deposit's price ceiling")]
+    PriceAboveCeiling,
+    #[msg("Deposit met its end conditions")]
+    StrategyCompleted,
+    #[msg("Deposit hasn't met its end conditions")]
+    StrategyNotCompleted,
+    #[msg("Deposit is paused")]
+    DepositPaused,
+    #[msg("Signer is neither the deposit's owner nor its delegate")]
+    InvalidManager,
+    #[msg("Reserve isn't whitelisted")]
+    ReserveNotWhitelisted,
+    #[msg("No admin action is queued")]
+    NoQueuedAction,
+    #[msg("Admin action timelock hasn't expired")]
+    TimelockNotExpired,
+    #[msg(
+        "Reserve whitelist is full, the reserve is already in or not in it, or it's the last one"
+    )]
+    InvalidWhitelistUpdate,
+    #[msg("Keeper registry is full, or the keeper is already in or not in it")]
+    InvalidKeeperUpdate,
+    #[msg("Account isn't a legacy deposit matching the instruction")]
+    InvalidDepositVersion,
+    #[msg("No yield was redeemed to spend")]
+    NoYieldToSpend,
+    #[msg("Schedule interval hasn't passed since the last run")]
+    ScheduleNotDue,
+    #[msg("Oracle price is stale")]
+    StaleOracle,
+    #[msg("Oracle price confidence interval is too wide")]
+    OracleTooUncertain,
+    #[msg("Timelock delay is below the minimum")]
+    InvalidTimelockDelay,
+    #[msg("Privileged instruction called by an unregistered keeper")]
+    InvalidKeeper,
+    #[msg("Order amount rounds down to zero lots")]
+    OrderTooSmall,
 }
 
 // Event emitted when a swap occurs for two base currencies on two different
@@ -859,6 +5695,315 @@ pub struct DidSwap {
     pub authority: Pubkey,
 }
 
+#[event]
+pub struct DcaExecuted {
+    // DepositState the purchase was made for
+    pub deposit: Pubkey,
+    // Deposit's share of the liquidity sold
+    pub yield_spent: u64,
+    // Deposit's share of the tokens purchased, paid to its dca_recipient
+    pub amount_received: u64,
+    // Unix timestamp of the run
+    pub timestamp: i64,
+}
+
 pub mod fee_recipient {
     solana_program::declare_id!("rohanrAYfWTd7DtNHVtoJFxdLYspwToEr55BqFdfkZd");
 }
+
+// Solend lending program on mainnet-beta
+pub mod solend_program {
+    solana_program::declare_id!("So1endDq2YkqhipRh3WViPa8hdiSpxWy6z3Z6tMCpAo");
+}
+
+// Pyth oracle program on mainnet-beta, owner of every price account
+pub mod pyth_program {
+    solana_program::declare_id!("FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mul_div_rounds_down_and_rejects_overflow() {
+        assert_eq!(mul_div(10, 1, 3).unwrap(), 3);
+        assert_eq!(mul_div(u64::MAX, 2, 2).unwrap(), u64::MAX);
+        assert!(mul_div(u64::MAX, 2, 1).is_err());
+        assert!(mul_div(1, 1, 0).is_err());
+    }
+
+    #[test]
+    fn pro_rata_sums_to_amount() {
+        let shares = pro_rata(100, &[1, 1, 1]).unwrap();
+        assert_eq!(shares, vec![33, 33, 34]);
+        assert_eq!(pro_rata(7, &[3, 0, 1]).unwrap(), vec![5, 0, 2]);
+        assert_eq!(pro_rata(0, &[5, 5]).unwrap(), vec![0, 0]);
+        assert!(pro_rata(1, &[u64::MAX, 1]).is_err());
+    }
+
+    #[test]
+    fn legacy_deposit_decodes_by_size() {
+        let legacy = LegacyDepositState {
+            liquidity_amount: 1_000,
+            schedule: DcaSchedule::Monthly,
+            nonce: 254,
+            ..Default::default()
+        };
+        let mut data = DepositState::discriminator().to_vec();
+        data.extend(legacy.try_to_vec().unwrap());
+        assert_eq!(data.len(), 8 + LegacyDepositState::LEN);
+
+        let decoded = LegacyDepositState::decode(&data).unwrap();
+        assert_eq!(decoded.liquidity_amount, 1_000);
+        assert_eq!(decoded.nonce, 254);
+
+        // Current deposits and other accounts of the same size don't decode
+        let mut current = Vec::new();
+        DepositState::default().try_serialize(&mut current).unwrap();
+        assert!(LegacyDepositState::decode(&current).is_err());
+        data[0] ^= 1;
+        assert!(LegacyDepositState::decode(&data).is_err());
+    }
+
+    #[test]
+    fn schedule_refuses_early_runs() {
+        let day = DcaSchedule::Daily.interval();
+        let mut last_run_at = 0;
+        assert_eq!(
+            advance_schedule(&mut last_run_at, DcaSchedule::Daily, 1_000).unwrap(),
+            1
+        );
+        assert_eq!(last_run_at, 1_000);
+        assert!(advance_schedule(&mut last_run_at, DcaSchedule::Daily, 1_000).is_err());
+        assert!(advance_schedule(&mut last_run_at, DcaSchedule::Daily, 999 + day).is_err());
+        assert_eq!(last_run_at, 1_000);
+        let now = 1_000 + 3 * day + 10;
+        assert_eq!(
+            advance_schedule(&mut last_run_at, DcaSchedule::Daily, now).unwrap(),
+            3
+        );
+        assert_eq!(last_run_at, now);
+    }
+
+    #[test]
+    fn principal_settles_with_checked_math() {
+        let mut deposit_state = DepositState {
+            liquidity_amount: 100,
+            ..Default::default()
+        };
+        deposit_state.settle_principal(40, 5).unwrap();
+        assert_eq!(deposit_state.liquidity_amount, 65);
+        assert!(deposit_state.settle_principal(66, 0).is_err());
+        assert!(deposit_state.settle_principal(0, u64::MAX).is_err());
+        assert_eq!(deposit_state.liquidity_amount, 65);
+    }
+
+    #[test]
+    fn yield_splits_between_compounding_and_spending() {
+        // 100 of yield, a quarter compounded
+        assert_eq!(
+            split_yield(1_100, 1_000, 0, 2_500, u64::MAX).unwrap(),
+            (25, 75)
+        );
+        // Principal is redeemed on top of the spent yield
+        assert_eq!(
+            split_yield(1_100, 1_000, 50, 2_500, u64::MAX).unwrap(),
+            (25, 125)
+        );
+        // The cap only limits the yield, not the principal
+        assert_eq!(split_yield(1_100, 1_000, 50, 0, 30).unwrap(), (0, 80));
+        // Fully compounded, or no yield at all, only redeems principal
+        assert_eq!(
+            split_yield(1_100, 1_000, 50, 10_000, u64::MAX).unwrap(),
+            (100, 50)
+        );
+        assert_eq!(
+            split_yield(900, 1_000, 50, 2_500, u64::MAX).unwrap(),
+            (0, 50)
+        );
+        assert!(split_yield(1_100, 1_000, 0, 20_000, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn principal_modes_spend_from_remaining_principal() {
+        let mut deposit_state = DepositState {
+            liquidity_amount: 1_000,
+            principal_mode: PrincipalMode::Fixed(300),
+            ..Default::default()
+        };
+        assert_eq!(deposit_state.principal_per_run(), 300);
+        deposit_state.liquidity_amount = 200;
+        assert_eq!(deposit_state.principal_per_run(), 200);
+        deposit_state.principal_mode = PrincipalMode::Bps(2_500);
+        assert_eq!(deposit_state.principal_per_run(), 50);
+        deposit_state.principal_mode = PrincipalMode::YieldOnly;
+        assert_eq!(deposit_state.principal_per_run(), 0);
+    }
+
+    #[test]
+    fn oracle_prices_must_be_fresh_and_confident() {
+        assert!(check_price(1_000, 20, 100, 125).is_ok());
+        assert!(check_price(1_000, 20, 100, 126).is_err());
+        assert!(check_price(1_000, 21, 100, 100).is_err());
+        assert!(check_price(0, 0, 100, 100).is_err());
+        assert!(check_price(-5, 0, 100, 100).is_err());
+    }
+
+    #[test]
+    fn oracle_prices_scale_to_price_precision() {
+        // 25.5 USDC (6 decimals) per SOL (9 decimals), published with exponent -8
+        let price = scale_price(2_550_000_000, -8 + 6 - 9).unwrap();
+        assert_eq!(price, 25_500_000);
+        assert_eq!(scale_price(3, 2).unwrap(), 300 * PRICE_PRECISION as u64);
+        assert!(scale_price(i64::MAX, 30).is_err());
+    }
+
+    #[test]
+    fn value_path_advances_per_interval() {
+        let mut deposit_state = DepositState {
+            strategy: DcaStrategy::ValueAveraging {
+                value_per_run: 100,
+                runs: 2,
+            },
+            ..Default::default()
+        };
+        // Runs skipped at or above target still move the path on
+        deposit_state.advance_value_path(3);
+        assert!(matches!(
+            deposit_state.strategy,
+            DcaStrategy::ValueAveraging { runs: 5, .. }
+        ));
+
+        let mut fixed = DepositState::default();
+        fixed.advance_value_path(3);
+        assert!(matches!(fixed.strategy, DcaStrategy::FixedSize));
+    }
+
+    #[test]
+    fn end_conditions_complete_the_deposit() {
+        let mut deposit_state = DepositState::default();
+        assert!(!deposit_state.is_completed(i64::MAX));
+        assert!(deposit_state.check_runnable(0).is_ok());
+
+        deposit_state.max_executions = 3;
+        deposit_state.counter = 2;
+        assert!(!deposit_state.is_completed(0));
+        deposit_state.counter = 3;
+        assert!(deposit_state.is_completed(0));
+        assert!(deposit_state.check_runnable(0).is_err());
+
+        let mut deposit_state = DepositState {
+            end_at: 1_000,
+            ..Default::default()
+        };
+        assert!(!deposit_state.is_completed(999));
+        assert!(deposit_state.is_completed(1_000));
+
+        deposit_state.end_at = 0;
+        deposit_state.target_dca_amount = 500;
+        deposit_state.total_dca_received = 499;
+        assert!(!deposit_state.is_completed(0));
+        deposit_state.total_dca_received = 500;
+        assert!(deposit_state.is_completed(0));
+
+        let deposit_state = DepositState {
+            paused: true,
+            ..Default::default()
+        };
+        assert!(!deposit_state.is_completed(0));
+        assert!(deposit_state.check_runnable(0).is_err());
+    }
+
+    #[test]
+    fn purchase_target_needs_a_leg_buying_dca_mint() {
+        let mut deposit_state = DepositState {
+            dca_mint: Pubkey::new_unique(),
+            target_dca_amount: 500,
+            ..Default::default()
+        };
+        assert!(deposit_state.check_target_reachable().is_ok());
+
+        deposit_state.basket[0].mint = Pubkey::new_unique();
+        deposit_state.basket_len = 1;
+        assert!(deposit_state.check_target_reachable().is_err());
+
+        deposit_state.basket[0].mint = deposit_state.dca_mint;
+        assert!(deposit_state.check_target_reachable().is_ok());
+    }
+
+    #[test]
+    fn protocol_fee_rounds_down() {
+        assert_eq!(protocol_fee(1_000, 30).unwrap(), 3);
+        assert_eq!(protocol_fee(333, 30).unwrap(), 0);
+        assert_eq!(protocol_fee(1_000, 0).unwrap(), 0);
+        assert_eq!(protocol_fee(1_000, 10_000).unwrap(), 1_000);
+        assert!(protocol_fee(1_000, 10_001).is_err());
+    }
+
+    #[test]
+    fn timelock_delay_has_a_floor() {
+        assert!(check_timelock_delay(MIN_TIMELOCK_DELAY).is_ok());
+        assert!(check_timelock_delay(MIN_TIMELOCK_DELAY - 1).is_err());
+        assert!(check_timelock_delay(0).is_err());
+        assert!(check_timelock_delay(-1).is_err());
+    }
+
+    #[test]
+    fn vault_shares_price_off_collateral() {
+        // The first depositor gets one share per collateral token
+        assert_eq!(vault_shares_for(1_000, 0, 0).unwrap(), 1_000);
+
+        // 800 shares hold 1000 collateral, 100 more collateral buys 80 shares
+        let shares = vault_shares_for(100, 1_000, 800).unwrap();
+        assert_eq!(shares, 80);
+
+        // Withdrawing right away returns what was deposited, existing holders keep theirs
+        assert_eq!(mul_div(shares, 1_100, 880).unwrap(), 100);
+        assert_eq!(mul_div(800, 1_100, 880).unwrap(), 1_000);
+    }
+
+    #[test]
+    fn vault_position_accrues_per_share() {
+        let mut position = VaultPosition {
+            shares: 100,
+            ..Default::default()
+        };
+        position.checkpoint(0);
+
+        // 5 DCA tokens bought per share
+        let dca_per_share = 5 * SHARE_PRECISION;
+        position.accrue(dca_per_share).unwrap();
+        assert_eq!(position.pending, 500);
+
+        // Accruing again without new purchases adds nothing
+        position.accrue(dca_per_share).unwrap();
+        assert_eq!(position.pending, 500);
+    }
+
+    #[test]
+    fn vault_share_transfers_settle_accrual() {
+        let mut sender = VaultPosition {
+            shares: 100,
+            ..Default::default()
+        };
+        let mut recipient = VaultPosition::default();
+
+        // Tokens bought before the move stay with the sender
+        sender
+            .transfer_shares(&mut recipient, 40, 5 * SHARE_PRECISION)
+            .unwrap();
+        assert_eq!((sender.shares, sender.pending), (60, 500));
+        assert_eq!((recipient.shares, recipient.pending), (40, 0));
+
+        // Later purchases accrue to the new holder
+        sender.accrue(7 * SHARE_PRECISION).unwrap();
+        recipient.accrue(7 * SHARE_PRECISION).unwrap();
+        assert_eq!(sender.pending, 500 + 60 * 2);
+        assert_eq!(recipient.pending, 40 * 2);
+
+        assert!(recipient
+            .transfer_shares(&mut sender, 41, 7 * SHARE_PRECISION)
+            .is_err());
+    }
+}